@@ -0,0 +1,49 @@
+//! Parse throughput on fixtures of increasing size:
+//!
+//! - `small`: two joins over three tables
+//! - `medium`: the shape of TPC-H Q5 (six tables, a group by)
+//! - `large`: a synthetic left-deep chain of 50 joins
+//!
+//! Each fixture is measured twice, once reported in bytes/s and once in
+//! plans/s. Run with `cargo bench --bench parse`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const FIXTURES: [(&str, &str); 3] = [
+    ("small", include_str!("data/small.json")),
+    ("medium", include_str!("data/medium.json")),
+    ("large", include_str!("data/large.json")),
+];
+
+fn bytes_per_second(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_bytes");
+    for (name, json) in FIXTURES {
+        group.throughput(Throughput::Bytes(json.len() as u64));
+        group.bench_with_input(BenchmarkId::new("from_str", name), json, |b, json| {
+            b.iter(|| ddbplan::from_str(black_box(json)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn plans_per_second(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_plans");
+    for (name, json) in FIXTURES {
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::new("from_str", name), json, |b, json| {
+            b.iter(|| ddbplan::from_str(black_box(json)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+// get_join_tree adds the file read on top of from_str
+fn from_file(c: &mut Criterion) {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/data/medium.json");
+    c.bench_function("get_join_tree/medium", |b| {
+        b.iter(|| ddbplan::get_join_tree(black_box(path)).unwrap())
+    });
+}
+
+criterion_group!(benches, bytes_per_second, plans_per_second, from_file);
+criterion_main!(benches);
@@ -0,0 +1,1837 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::graph::{is_acyclic, JoinGraph};
+use crate::{
+    preorder_traverse, Attribute, CompareOp, Condition, JoinType, Node, NodeId, Op, Predicate,
+    Scan, Side, Value,
+};
+
+/// Powers of ten from 10 to 10^9, for `cardinality_histogram`.
+pub const DEFAULT_CARDINALITY_BUCKETS: [u64; 9] = [
+    10,
+    100,
+    1_000,
+    10_000,
+    100_000,
+    1_000_000,
+    10_000_000,
+    100_000_000,
+    1_000_000_000,
+];
+
+/// Width in bytes assumed by `bytes_scanned` for columns without a known
+/// width.
+pub const DEFAULT_COLUMN_WIDTH: usize = 8;
+
+/// The first node below any chain of single-child wrappers without a parsed
+/// `attr` (`Query`, `RESULT_COLLECTOR`, `EXPLAIN_ANALYZE`...): the first node
+/// that has an operator, or several children.
+pub fn operator_root(root: &Node) -> &Node {
+    let mut node = root;
+    while node.attr.is_none() && node.children.len() == 1 {
+        node = &node.children[0];
+    }
+    node
+}
+
+pub(crate) fn is_inner_join(node: &Node) -> bool {
+    matches!(&node.attr, Some(Op::Join(join)) if join.join_type == JoinType::Inner)
+}
+
+/// The root of the largest region of directly nested inner joins (counted
+/// in joins, the first in preorder on ties): the part of the plan whose join
+/// order can be changed freely. Its inputs are whatever sits below the
+/// region's joins. `None` if the plan has no inner join.
+pub fn inner_join_core(root: &Node) -> Option<&Node> {
+    fn region_size(node: &Node) -> usize {
+        1 + node
+            .children
+            .iter()
+            .filter(|c| is_inner_join(c))
+            .map(|c| region_size(c))
+            .sum::<usize>()
+    }
+    // roots of regions: inner joins whose parent isn't one
+    fn regions<'a>(node: &'a Node, parent_joins: bool, found: &mut Vec<&'a Node>) {
+        let joins = is_inner_join(node);
+        if joins && !parent_joins {
+            found.push(node);
+        }
+        for child in &node.children {
+            regions(child, joins, found);
+        }
+    }
+    let mut found = Vec::new();
+    regions(root, false, &mut found);
+    found.into_iter().rev().max_by_key(|node| region_size(node))
+}
+
+/// The number of rows the query returned, as counted by its
+/// `RESULT_COLLECTOR` (or, without one, its `Query` node). This is what the
+/// client got, which can differ from the cardinality of the top operator.
+/// `None` if the plan has neither node.
+pub fn result_cardinality(root: &Node) -> Option<u64> {
+    let mut collector = None;
+    let mut query = None;
+    preorder_traverse(root, &mut |node: &Node| match node.name.as_str() {
+        "RESULT_COLLECTOR" => {
+            collector.get_or_insert(node.cardinality);
+        }
+        "Query" => {
+            query.get_or_insert(node.cardinality);
+        }
+        _ => {}
+    });
+    collector.or(query)
+}
+
+/// Whether the plan is wrapped in an `EXPLAIN_ANALYZE` node, as in profiles
+/// that may report cumulative timings (see `rewrite::to_self_timing`). Only
+/// the wrappers above `operator_root` are looked at.
+pub fn is_explain_analyze(root: &Node) -> bool {
+    let mut node = root;
+    loop {
+        if node.name == "EXPLAIN_ANALYZE" {
+            return true;
+        }
+        if node.attr.is_some() || node.children.len() != 1 {
+            return false;
+        }
+        node = &node.children[0];
+    }
+}
+
+/// Distance from `root` to the node with id `target`.
+pub fn depth(root: &Node, target: NodeId) -> Option<usize> {
+    if root.id == target {
+        return Some(0);
+    }
+    root.children
+        .iter()
+        .find_map(|c| depth(c, target))
+        .map(|d| d + 1)
+}
+
+/// The operators the tuples of the scan with id `scan_id` pass through, from
+/// its parent up to `root`. Empty if there is no such node or it is not a
+/// scan.
+pub fn operator_chain_to_root(root: &Node, scan_id: NodeId) -> Vec<&Node> {
+    // the path from node down to the scan, scan first
+    fn path_to(node: &Node, scan_id: NodeId) -> Option<Vec<&Node>> {
+        if node.id == scan_id {
+            return Some(vec![node]);
+        }
+        let mut path = node.children.iter().find_map(|c| path_to(c, scan_id))?;
+        path.push(node);
+        Some(path)
+    }
+    match path_to(root, scan_id) {
+        Some(path) if matches!(path[0].attr, Some(Op::Scan(_)) | Some(Op::CteScan(_))) => {
+            path[1..].to_vec()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Length of the longest path from `node` down to a leaf.
+pub fn height(node: &Node) -> usize {
+    node.children
+        .iter()
+        .map(|c| height(c) + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// The largest number of joins on any root-to-leaf path: the join count for
+/// a left-deep plan, about its log2 for a balanced bushy one.
+pub fn longest_join_chain(root: &Node) -> usize {
+    let below = root
+        .children
+        .iter()
+        .map(|c| longest_join_chain(c))
+        .max()
+        .unwrap_or(0);
+    let is_join = matches!(root.attr, Some(Op::Join(_)) | Some(Op::MultiwayJoin(_)));
+    below + usize::from(is_join)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinSides {
+    pub node: NodeId,
+    pub build_card: u64,
+    pub probe_card: u64,
+    pub build_is_larger: bool,
+}
+
+/// Input sizes of every hash join. DuckDB builds the hash table on the
+/// second (right) child and probes it with the first (left) child.
+pub fn join_sides(root: &Node) -> Vec<JoinSides> {
+    let mut sides = Vec::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        if node.name == "HASH_JOIN" && node.children.len() == 2 {
+            let probe_card = node.children[0].cardinality;
+            let build_card = node.children[1].cardinality;
+            sides.push(JoinSides {
+                node: node.id,
+                build_card,
+                probe_card,
+                build_is_larger: build_card > probe_card,
+            });
+        }
+    });
+    sides
+}
+
+/// Hash joins whose build side (the second child, which DuckDB loads into
+/// the hash table) has strictly more tuples than the probe side (the first
+/// child, streamed through it). Building on the smaller input is almost
+/// always cheaper, so these usually point at a misestimate.
+pub fn bad_build_choices(root: &Node) -> Vec<NodeId> {
+    join_sides(root)
+        .into_iter()
+        .filter(|sides| sides.build_is_larger)
+        .map(|sides| sides.node)
+        .collect()
+}
+
+/// Average number of matches per probe tuple, `output / probe` cardinality,
+/// of every hash join. Values well above 1 point at many-to-many joins that
+/// blow up. A join with an empty probe side has a fan-out of 0.
+pub fn join_fanout(root: &Node) -> Vec<(NodeId, f64)> {
+    let mut fanouts = Vec::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        if node.name == "HASH_JOIN" && node.children.len() == 2 {
+            let probe_card = node.children[0].cardinality;
+            let fanout = if probe_card == 0 {
+                0.0
+            } else {
+                node.cardinality as f64 / probe_card as f64
+            };
+            fanouts.push((node.id, fanout));
+        }
+    });
+    fanouts
+}
+
+/// Operators that must consume their whole input (or, for hash joins, their
+/// whole build side) before producing output, ending a pipeline:
+///
+/// - aggregates (`SIMPLE_AGGREGATE`, `HASH_GROUP_BY`, `PERFECT_HASH_GROUP_BY`)
+///   and sorts (`ORDER_BY`, `TOP_N`) always break;
+/// - `HASH_JOIN` breaks on its build (second) child, while the probe side
+///   streams through;
+/// - scans, filters and projections never break.
+pub fn pipeline_breakers(root: &Node) -> Vec<NodeId> {
+    let mut breakers = Vec::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        if materializes_input(node) || node.name == "HASH_JOIN" {
+            breakers.push(node.id);
+        }
+    });
+    breakers
+}
+
+// operators that break on all of their inputs
+fn materializes_input(node: &Node) -> bool {
+    matches!(
+        node.name.as_str(),
+        "SIMPLE_AGGREGATE" | "HASH_GROUP_BY" | "PERFECT_HASH_GROUP_BY" | "ORDER_BY" | "TOP_N"
+    )
+}
+
+/// The pipeline breaker whose materialized input took longest to produce,
+/// i.e. the one that stalls execution the most: the total `timing` of a
+/// hash join's build side, or of all inputs of an aggregate or sort. `None`
+/// if the plan has no breaker.
+pub fn bottleneck_breaker(root: &Node) -> Option<&Node> {
+    fn subtree_timing(node: &Node) -> f64 {
+        node.timing_or_zero() + node.children.iter().map(|c| subtree_timing(c)).sum::<f64>()
+    }
+    fn visit<'a>(node: &'a Node, best: &mut Option<(f64, &'a Node)>) {
+        let input_timing = if materializes_input(node) {
+            Some(node.children.iter().map(|c| subtree_timing(c)).sum())
+        } else if node.name == "HASH_JOIN" {
+            node.children.get(1).map(|build| subtree_timing(build))
+        } else {
+            None
+        };
+        if let Some(t) = input_timing {
+            if best.is_none_or(|(b, _)| t > b) {
+                *best = Some((t, node));
+            }
+        }
+        for child in &node.children {
+            visit(child, best);
+        }
+    }
+    let mut best = None;
+    visit(root, &mut best);
+    best.map(|(_, node)| node)
+}
+
+/// Splits the plan into pipelines and sums the output cardinalities of the
+/// operators in each, as `(pipeline, tuples)` with pipelines numbered in
+/// preorder of their topmost operator. A pipeline runs from a source up to
+/// the breaker (see `pipeline_breakers`) it ends in: an aggregate or sort
+/// ends the pipeline of its input and is the source of the one above it,
+/// and a hash join's build side is a pipeline of its own that ends in the
+/// join, which belongs to the pipeline of its probe side.
+pub fn pipeline_tuple_counts(root: &Node) -> Vec<(usize, u64)> {
+    fn visit(node: &Node, pipeline: usize, counts: &mut Vec<u64>) {
+        counts[pipeline] += node.cardinality;
+        for (i, child) in node.children.iter().enumerate() {
+            let build_side = node.name == "HASH_JOIN" && i == 1;
+            let child_pipeline = if build_side || materializes_input(child) {
+                counts.push(0);
+                counts.len() - 1
+            } else {
+                pipeline
+            };
+            visit(child, child_pipeline, counts);
+        }
+    }
+    let mut counts = vec![0];
+    visit(root, 0, &mut counts);
+    counts.into_iter().enumerate().collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    // streamed from a scan as it is read
+    Pipelined,
+    // read back from the result of an aggregate or sort
+    Materialized,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinInputMode {
+    pub node: NodeId,
+    pub probe: InputMode,
+    pub build: InputMode,
+}
+
+/// How each input of every hash join is produced, judged by the source of
+/// the pipeline it comes from (see `pipeline_tuple_counts`). Going down from
+/// the input through the operators that stream (filters, projections, the
+/// probe side of other hash joins, and any other first child), the input is
+/// `Materialized` if that ends at an aggregate or sort, and `Pipelined` if it
+/// ends at a leaf, normally a scan.
+pub fn join_input_modes(root: &Node) -> Vec<JoinInputMode> {
+    fn mode(mut node: &Node) -> InputMode {
+        loop {
+            if materializes_input(node) {
+                return InputMode::Materialized;
+            }
+            match node.children.first() {
+                Some(child) => node = child,
+                None => return InputMode::Pipelined,
+            }
+        }
+    }
+    let mut modes = Vec::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        if let ("HASH_JOIN", [probe, build]) = (node.name.as_str(), node.children.as_slice()) {
+            modes.push(JoinInputMode {
+                node: node.id,
+                probe: mode(probe),
+                build: mode(build),
+            });
+        }
+    });
+    modes
+}
+
+/// Joins without any equi-join condition, i.e. cross products (possibly
+/// restricted by non-equi conditions) hidden inside a hash join.
+pub fn predicateless_joins(root: &Node) -> Vec<NodeId> {
+    let mut joins = Vec::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        if let Some(Op::Join(join)) = &node.attr {
+            if join.equalizers.is_empty() {
+                joins.push(node.id);
+            }
+        }
+    });
+    joins
+}
+
+/// The root-to-leaf path with the largest total `timing`, i.e. the chain of
+/// operators most responsible for latency in a pipelined execution.
+pub fn critical_path(root: &Node) -> Vec<&Node> {
+    // (time along the best path below and including node, that path reversed)
+    fn best(node: &Node) -> (f64, Vec<&Node>) {
+        let (time, mut path) = node
+            .children
+            .iter()
+            .map(|c| best(c))
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .unwrap_or((0.0, Vec::new()));
+        path.push(node);
+        (time + node.timing_or_zero(), path)
+    }
+    let (_, mut path) = best(root);
+    path.reverse();
+    path
+}
+
+/// Nodes whose cardinality the profile marks as a sample-based estimate.
+pub fn estimated_only_nodes(root: &Node) -> Vec<NodeId> {
+    let mut nodes = Vec::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        if node.exact_cardinality == Some(false) {
+            nodes.push(node.id);
+        }
+    });
+    nodes
+}
+
+/// Total `timing` per operator name.
+pub fn timing_by_op(root: &Node) -> BTreeMap<String, f64> {
+    let mut timings = BTreeMap::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        *timings.entry(node.name.clone()).or_insert(0.0) += node.timing_or_zero();
+    });
+    timings
+}
+
+/// Each measured node's share of the summed `timing` of all nodes, in
+/// percent. Empty if nothing took any measured time.
+pub fn timing_percentages(root: &Node) -> BTreeMap<NodeId, f64> {
+    let mut timings = Vec::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        if let Some(timing) = node.timing {
+            timings.push((node.id, timing));
+        }
+    });
+    let total: f64 = timings.iter().map(|(_, t)| t).sum();
+    if total <= 0.0 {
+        return BTreeMap::new();
+    }
+    timings
+        .into_iter()
+        .map(|(id, t)| (id, t / total * 100.0))
+        .collect()
+}
+
+/// The summed timing of the table scans (`Op::Scan`) divided by the summed
+/// timing of every other node: above 1 the query spends more time reading
+/// than computing. Infinite if only scans took any time, and 0 if nothing
+/// did.
+pub fn io_compute_ratio(root: &Node) -> f64 {
+    let (mut io, mut compute) = (0.0, 0.0);
+    preorder_traverse(root, &mut |node: &Node| {
+        if node.is_scan() {
+            io += node.timing_or_zero();
+        } else {
+            compute += node.timing_or_zero();
+        }
+    });
+    match (io, compute) {
+        (0.0, 0.0) => 0.0,
+        (_, 0.0) => f64::INFINITY,
+        _ => io / compute,
+    }
+}
+
+/// Counts the nodes of the plan by cardinality, given increasing bucket
+/// boundaries: entry `i` counts the cardinalities below `buckets[i]` and at
+/// least `buckets[i - 1]`, and the last, extra entry those of at least the
+/// last boundary. See `DEFAULT_CARDINALITY_BUCKETS` for a log scale.
+pub fn cardinality_histogram(root: &Node, buckets: &[u64]) -> Vec<usize> {
+    let mut counts = vec![0; buckets.len() + 1];
+    preorder_traverse(root, &mut |node: &Node| {
+        counts[buckets.partition_point(|&b| b <= node.cardinality)] += 1;
+    });
+    counts
+}
+
+/// Names of all scanned tables, including the CTEs read by CTE scans.
+pub fn base_tables(root: &Node) -> BTreeSet<String> {
+    let mut tables = BTreeSet::new();
+    preorder_traverse(root, &mut |node: &Node| match &node.attr {
+        Some(Op::Scan(scan)) => {
+            tables.insert(scan.table_name.clone());
+        }
+        Some(Op::CteScan(cte)) => {
+            tables.insert(cte.name.clone());
+        }
+        _ => {}
+    });
+    tables
+}
+
+/// Output cardinality of the whole query divided by the product of the
+/// cardinalities of its leaves, computed in log space so that large inputs
+/// don't overflow. 0 if the query or any leaf produced no rows.
+pub fn overall_selectivity(root: &Node) -> f64 {
+    let output = operator_root(root).cardinality;
+    let mut log_inputs = 0.0;
+    let mut empty_leaf = false;
+    preorder_traverse(root, &mut |node: &Node| {
+        if node.children.is_empty() {
+            empty_leaf |= node.cardinality == 0;
+            log_inputs += (node.cardinality as f64).ln();
+        }
+    });
+    if output == 0 || empty_leaf {
+        return 0.0;
+    }
+    ((output as f64).ln() - log_inputs).exp()
+}
+
+/// The scans of the plan that read through an index (`INDEX_SCAN`).
+pub fn index_scans(root: &Node) -> Vec<&Scan> {
+    fn visit<'a>(node: &'a Node, scans: &mut Vec<&'a Scan>) {
+        if let Some(Op::Scan(scan)) = &node.attr {
+            if node.name == "INDEX_SCAN" {
+                scans.push(scan);
+            }
+        }
+        for child in &node.children {
+            visit(child, scans);
+        }
+    }
+    let mut scans = Vec::new();
+    visit(root, &mut scans);
+    scans
+}
+
+/// Whether any table is read through an index rather than sequentially.
+pub fn uses_index(root: &Node) -> bool {
+    !index_scans(root).is_empty()
+}
+
+/// For each scanned table in `column_counts` (the number of columns of each
+/// table in the schema), the number of its columns the plan reads and its
+/// number of columns, as `(projected, total)`. A low ratio means projections
+/// were pushed down well. A table scanned several times counts every column
+/// any of its scans reads.
+pub fn scan_projection_ratio(
+    root: &Node,
+    column_counts: &HashMap<String, usize>,
+) -> BTreeMap<String, (usize, usize)> {
+    let mut projected: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        if let Some(Op::Scan(scan)) = &node.attr {
+            projected
+                .entry(&scan.table_name)
+                .or_default()
+                .extend(scan.attributes.iter().map(|a| a.attr_name.as_str()));
+        }
+    });
+    projected
+        .into_iter()
+        .filter_map(|(table, columns)| {
+            let total = *column_counts.get(table)?;
+            Some((table.to_string(), (columns.len(), total)))
+        })
+        .collect()
+}
+
+/// Estimated bytes read by the plan's scans: each scan's cardinality times
+/// the summed widths of the columns it reads, looked up by `(table, column)`
+/// in `widths`. Unknown columns count `DEFAULT_COLUMN_WIDTH` bytes. The
+/// total saturates at `u64::MAX`.
+pub fn bytes_scanned(root: &Node, widths: &HashMap<(String, String), usize>) -> u64 {
+    bytes_scanned_with(root, widths, DEFAULT_COLUMN_WIDTH)
+}
+
+pub fn bytes_scanned_with(
+    root: &Node,
+    widths: &HashMap<(String, String), usize>,
+    default_width: usize,
+) -> u64 {
+    let mut bytes: u64 = 0;
+    preorder_traverse(root, &mut |node: &Node| {
+        if let Some(Op::Scan(scan)) = &node.attr {
+            let row_width: usize = scan
+                .attributes
+                .iter()
+                .map(|a| {
+                    let key = (scan.table_name.clone(), a.attr_name.clone());
+                    widths.get(&key).copied().unwrap_or(default_width)
+                })
+                .sum();
+            bytes = bytes.saturating_add(node.cardinality.saturating_mul(row_width as u64));
+        }
+    });
+    bytes
+}
+
+/// The attributes of each table that appear in some join condition.
+pub fn join_keys_by_table(root: &Node) -> BTreeMap<String, BTreeSet<Attribute>> {
+    let mut keys: BTreeMap<String, BTreeSet<Attribute>> = BTreeMap::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        let conditions = match &node.attr {
+            Some(Op::Join(join)) => &join.equalizers,
+            Some(Op::MultiwayJoin(multiway)) => &multiway.conditions,
+            _ => return,
+        };
+        for cond in conditions {
+            for attr in [&cond.left_attr, &cond.right_attr] {
+                keys.entry(attr.table_name.clone())
+                    .or_default()
+                    .insert(attr.clone());
+            }
+        }
+    });
+    keys
+}
+
+/// Every distinct equi-join condition of the plan as SQL, `a.x = b.y` with
+/// the sides in canonical order, sorted. Comparing these sets tells which
+/// join patterns plans share.
+pub fn join_predicates_sql(root: &Node) -> Vec<String> {
+    let mut predicates = BTreeSet::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        let conditions = match &node.attr {
+            Some(Op::Join(join)) => &join.equalizers,
+            Some(Op::MultiwayJoin(multiway)) => &multiway.conditions,
+            _ => return,
+        };
+        predicates.extend(conditions.iter().map(|c| c.canonical().to_string()));
+    });
+    predicates.into_iter().collect()
+}
+
+/// Number of distinct join keys of each table in the inner joins, where
+/// columns of a table that the join conditions transitively equate count
+/// once. A table joined on many different keys is a hub.
+pub fn join_key_count(root: &Node) -> BTreeMap<String, usize> {
+    let mut classes: BTreeMap<String, BTreeSet<usize>> = BTreeMap::new();
+    for (attr, class) in JoinGraph::from_plan(root).attribute_classes() {
+        classes.entry(attr.table_name).or_default().insert(class);
+    }
+    classes
+        .into_iter()
+        .map(|(table, classes)| (table, classes.len()))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComplexityScore {
+    pub join_count: usize,
+    pub max_depth: usize,
+    pub is_cyclic: bool,
+    pub treewidth_bound: usize,
+    pub distinct_tables: usize,
+}
+
+impl ComplexityScore {
+    /// A single number for ranking queries by difficulty:
+    /// `joins + depth / 2 + 2 * treewidth_bound + 5 if cyclic`.
+    pub fn score(&self) -> f64 {
+        let cyclic = if self.is_cyclic { 5.0 } else { 0.0 };
+        self.join_count as f64
+            + self.max_depth as f64 / 2.0
+            + 2.0 * self.treewidth_bound as f64
+            + cyclic
+    }
+}
+
+pub fn complexity_score(root: &Node) -> ComplexityScore {
+    let graph = JoinGraph::from_plan(root);
+    let mut join_count = 0;
+    preorder_traverse(root, &mut |node: &Node| {
+        if let Some(Op::Join(_)) = node.attr {
+            join_count += 1;
+        }
+    });
+    ComplexityScore {
+        join_count,
+        max_depth: height(root),
+        is_cyclic: !is_acyclic(&graph),
+        treewidth_bound: graph.treewidth_upper_bound(),
+        distinct_tables: base_tables(root).len(),
+    }
+}
+
+// qualified column references (`table.column`) in a predicate, outside
+// string literals
+pub(crate) fn referenced_columns(predicate: &str) -> Vec<Attribute> {
+    let mut columns = Vec::new();
+    let mut in_string = false;
+    let mut ident = String::new();
+    // set once `table.` has been read, while reading the column
+    let mut table: Option<String> = None;
+    let mut chars = predicate.chars().peekable();
+    while let Some(c) = chars.next() {
+        let in_ident = c.is_alphanumeric() || c == '_' || (c == '#' && table.is_some());
+        if !in_string && in_ident {
+            ident.push(c);
+            continue;
+        }
+        if let Some(table_name) = table.take() {
+            columns.push(Attribute::new(table_name, std::mem::take(&mut ident)));
+        }
+        if c == '\'' {
+            in_string = !in_string;
+        } else if !in_string {
+            let qualifies = c == '.'
+                && ident.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                && chars
+                    .peek()
+                    .is_some_and(|n| n.is_alphanumeric() || *n == '_' || *n == '#');
+            if qualifies {
+                table = Some(ident.clone());
+            }
+        }
+        ident.clear();
+    }
+    if let Some(table_name) = table {
+        columns.push(Attribute::new(table_name, ident));
+    }
+    columns
+}
+
+fn referenced_tables(predicate: &str) -> BTreeSet<String> {
+    referenced_columns(predicate)
+        .into_iter()
+        .map(|c| c.table_name)
+        .collect()
+}
+
+/// Filter predicates grouped by the table(s) whose columns they reference.
+/// Pushed-down scan filters belong to the scanned table. A predicate over
+/// several tables is listed under each of them, suffixed with
+/// ` (spans a, b)`; predicates without qualified column references are
+/// attributed to the only table scanned below the filter, if there is one.
+pub fn filters_by_table(root: &Node) -> BTreeMap<String, Vec<String>> {
+    let mut filters: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    preorder_traverse(root, &mut |node: &Node| match &node.attr {
+        Some(Op::Scan(scan)) if !scan.filters.is_empty() => filters
+            .entry(scan.table_name.clone())
+            .or_default()
+            .extend(scan.filters.iter().map(|p| p.to_string())),
+        Some(Op::Filter(filter)) => {
+            for predicate in filter.predicates.iter().map(|p| p.to_string()) {
+                let mut tables = referenced_tables(&predicate);
+                if tables.is_empty() {
+                    let below = base_tables(node);
+                    if below.len() == 1 {
+                        tables = below;
+                    }
+                }
+                let entry = if tables.len() > 1 {
+                    let names: Vec<_> = tables.iter().map(|t| t.as_str()).collect();
+                    format!("{} (spans {})", predicate, names.join(", "))
+                } else {
+                    predicate
+                };
+                for table in tables {
+                    filters.entry(table).or_default().push(entry.clone());
+                }
+            }
+        }
+        _ => {}
+    });
+    filters
+}
+
+/// The predicates, from scans and filters, that compare a column with
+/// constants only, as (column, operator, constant). IN lists and BETWEEN
+/// bounds are written as `(a, b, ...)`; column-to-column comparisons are
+/// left out.
+pub fn constant_filters(root: &Node) -> Vec<(Attribute, CompareOp, String)> {
+    fn is_constant(value: &Value) -> bool {
+        match value {
+            Value::Literal(_) => true,
+            Value::List(values) => values.iter().all(is_constant),
+            Value::Attribute(_) => false,
+        }
+    }
+    let mut constants = Vec::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        let predicates = match &node.attr {
+            Some(Op::Scan(scan)) => &scan.filters,
+            Some(Op::Filter(filter)) => &filter.predicates,
+            _ => return,
+        };
+        for predicate in predicates {
+            if let Predicate::Compare { left, op, right } = predicate {
+                if is_constant(right) {
+                    constants.push((left.clone(), *op, right.to_string()));
+                }
+            }
+        }
+    });
+    constants
+}
+
+/// The attributes each node outputs: scans introduce their columns,
+/// projections and group-bys keep the columns they list, semi and anti joins
+/// pass on their preserved side, mark joins add their marker, and every
+/// other operator passes on the union of its children's. A listed column
+/// without a table name (e.g. `#2`) can't be resolved, so it keeps every
+/// child attribute of that name, or all of them if none matches.
+pub fn available_attributes(root: &Node) -> BTreeMap<NodeId, BTreeSet<Attribute>> {
+    fn restrict(columns: &[Attribute], input: &BTreeSet<Attribute>) -> BTreeSet<Attribute> {
+        let mut output = BTreeSet::new();
+        for column in columns {
+            if !column.table_name.is_empty() {
+                output.insert(column.clone());
+                continue;
+            }
+            let matching: Vec<_> = input
+                .iter()
+                .filter(|a| a.attr_name == column.attr_name)
+                .cloned()
+                .collect();
+            if matching.is_empty() {
+                output.extend(input.iter().cloned());
+            } else {
+                output.extend(matching);
+            }
+        }
+        output
+    }
+
+    fn visit(node: &Node, available: &mut BTreeMap<NodeId, BTreeSet<Attribute>>) {
+        let mut input = BTreeSet::new();
+        for child in &node.children {
+            visit(child, available);
+            input.extend(available[&child.id].iter().cloned());
+        }
+        let output = match &node.attr {
+            Some(Op::Scan(scan)) => scan.attributes.iter().cloned().collect(),
+            Some(Op::Project(project)) => restrict(&project.columns, &input),
+            Some(Op::Aggregate(aggregate)) => restrict(&aggregate.groups, &input),
+            // only the preserved side of a semi or anti join is output
+            Some(Op::Join(join)) if matches!(join.join_type, JoinType::Semi | JoinType::Anti) => {
+                let side = match join.preserved_side {
+                    Some(Side::Right) => 1,
+                    _ => 0,
+                };
+                node.children
+                    .get(side)
+                    .map(|c| available[&c.id].clone())
+                    .unwrap_or_default()
+            }
+            Some(Op::Join(join)) => {
+                input.extend(join.mark_column.iter().cloned());
+                input
+            }
+            // a CTE's columns aren't listed in the profile
+            Some(Op::CteScan(_)) => BTreeSet::new(),
+            Some(Op::Filter(_)) | Some(Op::MultiwayJoin(_)) | Some(Op::RecursiveCte(_)) | None => {
+                input
+            }
+        };
+        available.insert(node.id, output);
+    }
+
+    let mut available = BTreeMap::new();
+    visit(root, &mut available);
+    available
+}
+
+/// For each attribute the plan outputs (see `available_attributes`), the
+/// names of the operators it passed through, from the one that produced it
+/// up to the root: a scan for table columns, otherwise the operator that
+/// introduced it (such as a mark join's marker). An attribute several inputs
+/// provide follows the first of them.
+pub fn column_lineage(root: &Node) -> BTreeMap<Attribute, Vec<String>> {
+    fn visit(
+        node: &Node,
+        available: &BTreeMap<NodeId, BTreeSet<Attribute>>,
+    ) -> BTreeMap<Attribute, Vec<String>> {
+        let inputs: Vec<_> = node.children.iter().map(|c| visit(c, available)).collect();
+        available[&node.id]
+            .iter()
+            .map(|attr| {
+                let mut path = inputs
+                    .iter()
+                    .find_map(|input| input.get(attr).cloned())
+                    .unwrap_or_default();
+                path.push(node.name.clone());
+                (attr.clone(), path)
+            })
+            .collect()
+    }
+    visit(root, &available_attributes(root))
+}
+
+/// The attributes each projection discards: those its children output but
+/// it doesn't, as told by `available_attributes`. Every projection has an
+/// entry, empty if it keeps all of its input.
+pub fn projected_away(root: &Node) -> BTreeMap<NodeId, BTreeSet<Attribute>> {
+    let available = available_attributes(root);
+    let mut dropped = BTreeMap::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        if let Some(Op::Project(_)) = &node.attr {
+            let output = &available[&node.id];
+            let discarded = node
+                .children
+                .iter()
+                .flat_map(|c| &available[&c.id])
+                .filter(|attr| !output.contains(attr))
+                .cloned()
+                .collect();
+            dropped.insert(node.id, discarded);
+        }
+    });
+    dropped
+}
+
+/// Projections that drop a column which a join above them uses as a key, as
+/// told by `available_attributes`. The key then has to be derived again,
+/// or the plan is wrong.
+pub fn early_key_drops(root: &Node) -> Vec<NodeId> {
+    fn visit<'a>(
+        node: &'a Node,
+        keys_above: &mut Vec<&'a Attribute>,
+        available: &BTreeMap<NodeId, BTreeSet<Attribute>>,
+        drops: &mut Vec<NodeId>,
+    ) {
+        if let Some(Op::Project(_)) = &node.attr {
+            let output = &available[&node.id];
+            let drops_key = node
+                .children
+                .iter()
+                .flat_map(|c| &available[&c.id])
+                .any(|attr| !output.contains(attr) && keys_above.contains(&attr));
+            if drops_key {
+                drops.push(node.id);
+            }
+        }
+        let conditions: &[Condition] = match &node.attr {
+            Some(Op::Join(join)) => &join.equalizers,
+            Some(Op::MultiwayJoin(multiway)) => &multiway.conditions,
+            _ => &[],
+        };
+        let len = keys_above.len();
+        keys_above.extend(
+            conditions
+                .iter()
+                .flat_map(|c| [&c.left_attr, &c.right_attr]),
+        );
+        for child in &node.children {
+            visit(child, keys_above, available, drops);
+        }
+        keys_above.truncate(len);
+    }
+    let available = available_attributes(root);
+    let mut drops = Vec::new();
+    visit(root, &mut Vec::new(), &available, &mut drops);
+    drops
+}
+
+/// Scans reading the same table with the same columns and pushed-down
+/// filters, grouped in preorder of their first occurrence. Each group has at
+/// least two scans; these are candidates for common-subexpression elimination.
+pub fn duplicate_scans(root: &Node) -> Vec<Vec<NodeId>> {
+    let mut groups: Vec<Vec<NodeId>> = Vec::new();
+    let mut index: HashMap<&Scan, usize> = HashMap::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        if let Some(Op::Scan(scan)) = &node.attr {
+            let i = *index.entry(scan).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[i].push(node.id);
+        }
+    });
+    groups.retain(|ids| ids.len() > 1);
+    groups
+}
+
+/// Hash joins that build the same hash table: their build side is a scan
+/// of the same table (as in `duplicate_scans`) and they join on the same
+/// columns of it. Returned as `(table, joins)` in preorder of the first join,
+/// each with at least two joins; these could share one hash table.
+pub fn shared_build_candidates(root: &Node) -> Vec<(String, Vec<NodeId>)> {
+    let mut groups: Vec<(String, Vec<NodeId>)> = Vec::new();
+    let mut index: HashMap<(&Scan, BTreeSet<&Attribute>), usize> = HashMap::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        let (Some(Op::Join(join)), [_, build]) = (&node.attr, node.children.as_slice()) else {
+            return;
+        };
+        let Some(Op::Scan(scan)) = &build.attr else {
+            return;
+        };
+        let keys: BTreeSet<_> = join
+            .equalizers
+            .iter()
+            .flat_map(|c| [&c.left_attr, &c.right_attr])
+            .filter(|attr| attr.table_name == scan.table_name)
+            .collect();
+        if node.name != "HASH_JOIN" || keys.is_empty() {
+            return;
+        }
+        let i = *index.entry((scan, keys)).or_insert_with(|| {
+            groups.push((scan.table_name.clone(), Vec::new()));
+            groups.len() - 1
+        });
+        groups[i].1.push(node.id);
+    });
+    groups.retain(|(_, joins)| joins.len() > 1);
+    groups
+}
+
+/// Name patterns for `suspicious_join_conditions_with`.
+#[derive(Debug, Clone)]
+pub struct KeyNamePatterns {
+    // groups of column name suffixes, one group per kind of value; a column
+    // named exactly like a suffix without its leading `_` (`id`) matches too
+    pub kinds: Vec<Vec<String>>,
+}
+
+impl Default for KeyNamePatterns {
+    fn default() -> Self {
+        let kind = |suffixes: &[&str]| suffixes.iter().map(|s| s.to_string()).collect();
+        KeyNamePatterns {
+            kinds: vec![
+                kind(&["_id", "_key", "_sk"]),
+                kind(&["_date", "_time", "_ts"]),
+                kind(&["_name", "_title"]),
+            ],
+        }
+    }
+}
+
+impl KeyNamePatterns {
+    fn kind(&self, column: &str) -> Option<usize> {
+        let column = column.to_lowercase();
+        self.kinds.iter().position(|suffixes| {
+            suffixes
+                .iter()
+                .any(|s| column.ends_with(s.as_str()) || column == s.trim_start_matches('_'))
+        })
+    }
+}
+
+/// Join conditions equating columns whose names suggest different kinds of
+/// value (an id with a date, say), using the default `KeyNamePatterns`.
+pub fn suspicious_join_conditions(root: &Node) -> Vec<&Condition> {
+    suspicious_join_conditions_with(root, &KeyNamePatterns::default())
+}
+
+pub fn suspicious_join_conditions_with<'a>(
+    root: &'a Node,
+    patterns: &KeyNamePatterns,
+) -> Vec<&'a Condition> {
+    let mut suspicious = Vec::new();
+    preorder_traverse(root, &mut |node: &'a Node| {
+        let conditions = match &node.attr {
+            Some(Op::Join(join)) => &join.equalizers,
+            Some(Op::MultiwayJoin(multiway)) => &multiway.conditions,
+            _ => return,
+        };
+        for cond in conditions {
+            let left = patterns.kind(&cond.left_attr.attr_name);
+            let right = patterns.kind(&cond.right_attr.attr_name);
+            if left.is_some() && right.is_some() && left != right {
+                suspicious.push(cond);
+            }
+        }
+    });
+    suspicious
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+
+    // a ⋈ b ⋈ c ⋈ d, joined left-deep
+    fn left_deep() -> Node {
+        parsed(inner_join(
+            &["c.id = d.id"],
+            1,
+            inner_join(
+                &["b.id = c.id"],
+                1,
+                inner_join(
+                    &["a.id = b.id"],
+                    1,
+                    scan("a", &["id"], 1),
+                    scan("b", &["id"], 1),
+                ),
+                scan("c", &["id"], 1),
+            ),
+            scan("d", &["id"], 1),
+        ))
+    }
+
+    // (a ⋈ b) ⋈ (c ⋈ d)
+    fn bushy() -> Node {
+        parsed(inner_join(
+            &["b.id = c.id"],
+            1,
+            inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id"], 1),
+                scan("b", &["id"], 1),
+            ),
+            inner_join(
+                &["c.id = d.id"],
+                1,
+                scan("c", &["id"], 1),
+                scan("d", &["id"], 1),
+            ),
+        ))
+    }
+
+    #[test]
+    fn height_of_left_deep_is_join_count() {
+        assert_eq!(height(&left_deep()), 3);
+        assert_eq!(height(&bushy()), 2);
+    }
+
+    #[test]
+    fn depth_of_nodes() {
+        let plan = left_deep();
+        assert_eq!(depth(&plan, 0), Some(0));
+        // the scan of a, at the bottom of the chain
+        assert_eq!(depth(&plan, 3), Some(3));
+        assert_eq!(depth(&plan, 6), Some(1));
+        assert_eq!(depth(&plan, 7), None);
+    }
+
+    #[test]
+    fn join_sides_reads_build_from_second_child() {
+        let plan = parsed(inner_join(
+            &["a.id = b.id"],
+            5,
+            scan("a", &["id"], 100),
+            scan("b", &["id"], 10),
+        ));
+        assert_eq!(
+            join_sides(&plan),
+            vec![JoinSides {
+                node: 0,
+                build_card: 10,
+                probe_card: 100,
+                build_is_larger: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn critical_path_follows_the_slowest_leaf() {
+        // the slowest operator is the scan of c, but the path through the
+        // lower join and the scan of b takes longer
+        let plan = parsed(timed(
+            inner_join(
+                &["b.id = c.id"],
+                1,
+                timed(
+                    inner_join(
+                        &["a.id = b.id"],
+                        1,
+                        timed(scan("a", &["id"], 1), 0.1),
+                        timed(scan("b", &["id"], 1), 0.4),
+                    ),
+                    0.2,
+                ),
+                timed(scan("c", &["id"], 1), 0.5),
+            ),
+            0.1,
+        ));
+        let ids: Vec<_> = critical_path(&plan).iter().map(|n| n.id).collect();
+        assert_eq!(ids, vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn estimated_only_nodes_of_a_mixed_profile() {
+        let mut plan = inner_join(
+            &["a.id = b.id"],
+            1,
+            scan("a", &["id"], 1),
+            scan("b", &["id"], 1),
+        );
+        plan.exact_cardinality = Some(true);
+        plan.children[0].exact_cardinality = Some(false);
+        let plan = parsed(plan);
+        assert_eq!(estimated_only_nodes(&plan), vec![1]);
+    }
+
+    #[test]
+    fn reports_are_sorted() {
+        let plan = parsed(node(
+            "PROJECTION",
+            "z.id",
+            1,
+            vec![inner_join(
+                &["z.id = m.id", "z.k = a.k"],
+                1,
+                scan("z", &["id", "k"], 1),
+                inner_join(
+                    &["m.id = a.id"],
+                    1,
+                    scan("m", &["id"], 1),
+                    scan("a", &["id", "k"], 1),
+                ),
+            )],
+        ));
+        let tables: Vec<_> = base_tables(&plan).into_iter().collect();
+        assert_eq!(tables, vec!["a", "m", "z"]);
+        let ops: Vec<_> = timing_by_op(&plan).into_keys().collect();
+        assert_eq!(ops, vec!["HASH_JOIN", "PROJECTION", "SEQ_SCAN"]);
+        let keys: Vec<_> = join_keys_by_table(&plan)
+            .into_iter()
+            .map(|(table, keys)| {
+                (
+                    table,
+                    keys.into_iter().map(|k| k.attr_name).collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("a".to_string(), vec!["id".to_string(), "k".to_string()]),
+                ("m".to_string(), vec!["id".to_string()]),
+                ("z".to_string(), vec!["id".to_string(), "k".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn triangle_is_more_complex_than_a_chain() {
+        let chain = parsed(inner_join(
+            &["b.y = c.y"],
+            1,
+            inner_join(
+                &["a.x = b.x"],
+                1,
+                scan("a", &["x"], 1),
+                scan("b", &["x", "y"], 1),
+            ),
+            scan("c", &["y"], 1),
+        ));
+        let triangle = parsed(inner_join(
+            &["b.y = c.y", "c.z = a.z"],
+            1,
+            inner_join(
+                &["a.x = b.x"],
+                1,
+                scan("a", &["x", "z"], 1),
+                scan("b", &["x", "y"], 1),
+            ),
+            scan("c", &["y", "z"], 1),
+        ));
+        let simple = complexity_score(&chain);
+        let cyclic = complexity_score(&triangle);
+        assert_eq!((simple.join_count, simple.distinct_tables), (2, 3));
+        assert!(!simple.is_cyclic);
+        assert!(cyclic.is_cyclic);
+        assert!(cyclic.treewidth_bound > simple.treewidth_bound);
+        assert!(cyclic.score() > simple.score());
+    }
+
+    #[test]
+    fn filters_grouped_by_table() {
+        let single = parsed(node(
+            "FILTER",
+            "x > 3",
+            1,
+            vec![node(
+                "SEQ_SCAN",
+                "a\n[INFOSEPARATOR]\nx\n[INFOSEPARATOR]\nFilters: x<10",
+                1,
+                vec![],
+            )],
+        ));
+        let by_table = filters_by_table(&single);
+        assert_eq!(by_table.len(), 1);
+        assert_eq!(by_table["a"], vec!["x > 3", "a.x < 10"]);
+
+        let two = parsed(node(
+            "FILTER",
+            "(a.x > 3) AND (a.y <> b.y)",
+            1,
+            vec![inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id", "x", "y"], 1),
+                scan("b", &["id", "y"], 1),
+            )],
+        ));
+        let by_table = filters_by_table(&two);
+        assert_eq!(by_table["a"], vec!["a.x > 3", "a.y <> b.y (spans a, b)"]);
+        assert_eq!(by_table["b"], vec!["a.y <> b.y (spans a, b)"]);
+    }
+
+    #[test]
+    fn sorts_aggregates_and_hash_joins_break_pipelines() {
+        let plan = parsed(node(
+            "ORDER_BY",
+            "a.x ASC",
+            2,
+            vec![node(
+                "HASH_GROUP_BY",
+                "#0\ncount_star()",
+                2,
+                vec![node(
+                    "FILTER",
+                    "a.x > 1",
+                    5,
+                    vec![inner_join(
+                        &["a.id = b.id"],
+                        5,
+                        scan("a", &["id", "x"], 10),
+                        scan("b", &["id"], 10),
+                    )],
+                )],
+            )],
+        ));
+        assert_eq!(pipeline_breakers(&plan), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn attributes_available_from_leaves_to_root() {
+        let plan = parsed(node(
+            "PROJECTION",
+            "a.x",
+            1,
+            vec![inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id", "x"], 1),
+                scan("b", &["id"], 1),
+            )],
+        ));
+        let available = available_attributes(&plan);
+        let set = |attrs: &[(&str, &str)]| -> BTreeSet<Attribute> {
+            attrs.iter().map(|&(t, a)| Attribute::new(t, a)).collect()
+        };
+        assert_eq!(available[&2], set(&[("a", "id"), ("a", "x")]));
+        assert_eq!(available[&3], set(&[("b", "id")]));
+        assert_eq!(available[&1], set(&[("a", "id"), ("a", "x"), ("b", "id")]));
+        assert_eq!(available[&0], set(&[("a", "x")]));
+    }
+
+    #[test]
+    fn finds_duplicate_scans() {
+        let plan = parsed(inner_join(
+            &["a.id = b.id"],
+            1,
+            inner_join(
+                &["a.id = a.id"],
+                1,
+                scan("a", &["id"], 1),
+                scan("a", &["id"], 1),
+            ),
+            scan("b", &["id"], 1),
+        ));
+        assert_eq!(duplicate_scans(&plan), vec![vec![2, 3]]);
+    }
+
+    #[test]
+    fn predicateless_joins_include_non_equi_mark_joins() {
+        let plan = parsed(inner_join(
+            &["a.id = c.id"],
+            1,
+            node(
+                "HASH_JOIN",
+                "MARK\na.x <= b.y\nSUBQUERY\n",
+                10,
+                vec![scan("a", &["id", "x"], 10), scan("b", &["y"], 10)],
+            ),
+            scan("c", &["id"], 10),
+        ));
+        assert_eq!(predicateless_joins(&plan), vec![1]);
+        let Some(Op::Join(mark)) = &plan.children[0].attr else {
+            panic!("expected a join");
+        };
+        assert_eq!(
+            mark.mark_column,
+            Some(Attribute::new("SUBQUERY", "SUBQUERY"))
+        );
+
+        let lt = parsed(node(
+            "HASH_JOIN",
+            "MARK\na.x < b.y\n",
+            10,
+            vec![scan("a", &["x"], 10), scan("b", &["y"], 10)],
+        ));
+        assert_eq!(predicateless_joins(&lt), vec![0]);
+        assert_eq!(lt.as_join().unwrap().mark_column, None);
+    }
+
+    #[test]
+    fn pipeline_tuple_counts_split_at_the_build_side() {
+        let plan = parsed(inner_join(
+            &["a.id = b.id"],
+            50,
+            scan("a", &["id"], 100),
+            scan("b", &["id"], 10),
+        ));
+        assert_eq!(pipeline_tuple_counts(&plan), vec![(0, 150), (1, 10)]);
+    }
+
+    #[test]
+    fn operator_root_skips_nested_wrappers() {
+        let plan = parsed(node(
+            "Query",
+            "",
+            0,
+            vec![node(
+                "RESULT_COLLECTOR",
+                "",
+                0,
+                vec![node("PROJECTION", "a.id", 5, vec![scan("a", &["id"], 5)])],
+            )],
+        ));
+        let root = operator_root(&plan);
+        assert_eq!(root.name, "PROJECTION");
+        assert_eq!(root.id, 2);
+        assert_eq!(operator_root(root).id, 2);
+    }
+
+    #[test]
+    fn constant_filters_skip_column_comparisons() {
+        let plan = parsed(node(
+            "FILTER",
+            "l_shipdate > '1994-01-01' AND a.x = b.y",
+            1,
+            vec![scan("lineitem", &["l_shipdate"], 10)],
+        ));
+        assert_eq!(
+            constant_filters(&plan),
+            vec![(
+                Attribute::new("", "l_shipdate"),
+                CompareOp::Gt,
+                "'1994-01-01'".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn join_key_count_counts_composite_keys() {
+        let plan = parsed(inner_join(
+            &["f.e = e.id"],
+            1,
+            inner_join(
+                &["f.d1 = d.k1", "f.d2 = d.k2"],
+                1,
+                scan("f", &["d1", "d2", "e"], 100),
+                scan("d", &["k1", "k2"], 10),
+            ),
+            scan("e", &["id"], 10),
+        ));
+        let counts = join_key_count(&plan);
+        assert_eq!(counts["f"], 3);
+        assert_eq!(counts["d"], 2);
+        assert_eq!(counts["e"], 1);
+        assert_eq!(counts.keys().collect::<Vec<_>>(), ["d", "e", "f"]);
+    }
+
+    #[test]
+    fn suspicious_join_conditions_flag_mismatched_kinds() {
+        let plan = parsed(inner_join(
+            &["o.o_custkey = c.c_custkey", "o.o_order_date = c.c_id"],
+            1,
+            scan("o", &["o_custkey", "o_order_date"], 10),
+            scan("c", &["c_custkey", "c_id"], 10),
+        ));
+        let suspicious: Vec<_> = suspicious_join_conditions(&plan)
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(suspicious, vec!["o.o_order_date = c.c_id"]);
+    }
+
+    #[test]
+    fn inner_join_core_below_an_aggregate() {
+        let plan = parsed(node(
+            "HASH_GROUP_BY",
+            "#0\ncount_star()",
+            1,
+            vec![left_deep()],
+        ));
+        let core = inner_join_core(&plan).unwrap();
+        assert_eq!(core.id, 1);
+        assert_eq!(base_tables(core).len(), 4);
+        assert!(inner_join_core(&parsed(scan("a", &["id"], 1))).is_none());
+    }
+
+    #[test]
+    fn join_fanout_of_one_to_many_and_many_to_many_joins() {
+        // each l row matches one c row, then 40 ps rows
+        let plan = parsed(inner_join(
+            &["l.partkey = ps.partkey"],
+            4000,
+            inner_join(
+                &["l.custkey = c.custkey"],
+                100,
+                scan("l", &["custkey", "partkey"], 100),
+                scan("c", &["custkey"], 10),
+            ),
+            scan("ps", &["partkey"], 400),
+        ));
+        assert_eq!(join_fanout(&plan), vec![(0, 40.0), (1, 1.0)]);
+    }
+
+    #[test]
+    fn operator_chain_from_a_filtered_scan() {
+        let plan = parsed(node(
+            "PROJECTION",
+            "a.id",
+            1,
+            vec![inner_join(
+                &["a.id = b.id"],
+                1,
+                node("FILTER", "a.x > 3", 5, vec![scan("a", &["id", "x"], 10)]),
+                scan("b", &["id"], 10),
+            )],
+        ));
+        let chain: Vec<_> = operator_chain_to_root(&plan, 3)
+            .iter()
+            .map(|n| n.name.as_str())
+            .collect();
+        assert_eq!(chain, vec!["FILTER", "HASH_JOIN", "PROJECTION"]);
+        assert!(operator_chain_to_root(&plan, 2).is_empty());
+    }
+
+    #[test]
+    fn bottleneck_breaker_is_not_the_slowest_operator() {
+        let plan = parsed(timed(
+            inner_join(
+                &["b.id = c.id"],
+                1,
+                timed(
+                    inner_join(
+                        &["a.id = b.id"],
+                        1,
+                        timed(scan("a", &["id"], 1), 0.2),
+                        timed(scan("b", &["id"], 1), 1.0),
+                    ),
+                    0.3,
+                ),
+                timed(scan("c", &["id"], 1), 0.1),
+            ),
+            4.0,
+        ));
+        // the top join is slowest itself, but its build side is cheap
+        assert_eq!(bottleneck_breaker(&plan).map(|n| n.id), Some(1));
+        assert_eq!(
+            bottleneck_breaker(&parsed(scan("a", &["id"], 1))).map(|n| n.id),
+            None
+        );
+    }
+
+    #[test]
+    fn overall_selectivity_of_a_small_plan() {
+        let plan = parsed(node(
+            "Query",
+            "",
+            0,
+            vec![inner_join(
+                &["a.id = b.id"],
+                50,
+                scan("a", &["id"], 100),
+                scan("b", &["id"], 20),
+            )],
+        ));
+        assert!((overall_selectivity(&plan) - 50.0 / 2000.0).abs() < 1e-12);
+
+        let empty = parsed(inner_join(
+            &["a.id = b.id"],
+            0,
+            scan("a", &["id"], 100),
+            scan("b", &["id"], 0),
+        ));
+        assert_eq!(overall_selectivity(&empty), 0.0);
+    }
+
+    #[test]
+    fn join_predicates_sql_collapses_reversed_conditions() {
+        let plan = parsed(inner_join(
+            &["c.id = b.id", "b.id = a.id"],
+            1,
+            inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id"], 1),
+                scan("b", &["id"], 1),
+            ),
+            scan("c", &["id"], 1),
+        ));
+        assert_eq!(
+            join_predicates_sql(&plan),
+            vec!["a.id = b.id", "b.id = c.id"]
+        );
+    }
+
+    #[test]
+    fn result_cardinality_prefers_the_collector() {
+        let top_n = node("TOP_N", "", 10, vec![scan("a", &["id"], 100)]);
+        let collected = parsed(node(
+            "Query",
+            "",
+            0,
+            vec![node("RESULT_COLLECTOR", "", 10, vec![top_n.clone()])],
+        ));
+        assert_eq!(result_cardinality(&collected), Some(10));
+
+        let uncollected = parsed(node("Query", "", 7, vec![top_n.clone()]));
+        assert_eq!(result_cardinality(&uncollected), Some(7));
+        assert_eq!(result_cardinality(&parsed(top_n)), None);
+    }
+
+    #[test]
+    fn index_scans_skip_sequential_scans() {
+        let plan = parsed(inner_join(
+            &["a.id = b.id"],
+            1,
+            scan("a", &["id"], 100),
+            node("INDEX_SCAN", "b\n[INFOSEPARATOR]\nid", 1, vec![]),
+        ));
+        let tables: Vec<_> = index_scans(&plan)
+            .iter()
+            .map(|s| s.table_name.as_str())
+            .collect();
+        assert_eq!(tables, vec!["b"]);
+        assert!(uses_index(&plan));
+        assert!(!uses_index(&parsed(scan("a", &["id"], 100))));
+    }
+
+    #[test]
+    fn longest_join_chain_of_left_deep_and_bushy() {
+        assert_eq!(longest_join_chain(&left_deep()), 3);
+        assert_eq!(longest_join_chain(&bushy()), 2);
+        assert_eq!(longest_join_chain(&parsed(scan("a", &["id"], 1))), 0);
+    }
+
+    #[test]
+    fn shared_build_candidates_group_builds_on_the_same_keys() {
+        let dim = || scan("d", &["id", "k"], 10);
+        let plan = parsed(inner_join(
+            &["f.k = d.k"],
+            1,
+            inner_join(
+                &["f.d2 = d.id"],
+                1,
+                inner_join(
+                    &["f.d1 = d.id"],
+                    1,
+                    scan("f", &["d1", "d2", "k"], 100),
+                    dim(),
+                ),
+                dim(),
+            ),
+            dim(),
+        ));
+        assert_eq!(
+            shared_build_candidates(&plan),
+            vec![("d".to_string(), vec![1, 2])]
+        );
+    }
+
+    #[test]
+    fn early_key_drops_flag_only_key_dropping_projections() {
+        let plan = parsed(inner_join(
+            &["a.id = b.id"],
+            1,
+            node("PROJECTION", "a.x", 1, vec![scan("a", &["id", "x"], 1)]),
+            node("PROJECTION", "b.id", 1, vec![scan("b", &["id", "y"], 1)]),
+        ));
+        assert_eq!(early_key_drops(&plan), vec![1]);
+    }
+
+    #[test]
+    fn scan_projection_ratio_unions_the_columns_of_each_table() {
+        let plan = parsed(inner_join(
+            &["a.id = a.id"],
+            1,
+            scan("a", &["id", "x"], 1),
+            inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id", "y"], 1),
+                scan("b", &["id"], 1),
+            ),
+        ));
+        let counts = HashMap::from([("a".to_string(), 10), ("c".to_string(), 4)]);
+        assert_eq!(
+            scan_projection_ratio(&plan, &counts),
+            BTreeMap::from([("a".to_string(), (3, 10))])
+        );
+    }
+
+    #[test]
+    fn cardinality_histogram_buckets_at_the_boundaries() {
+        let plan = parsed(node(
+            "Query",
+            "",
+            0,
+            vec![inner_join(
+                &["a.id = b.id"],
+                5,
+                scan("a", &["id"], 10),
+                inner_join(
+                    &["b.id = c.id"],
+                    100,
+                    scan("b", &["id"], 1000),
+                    scan("c", &["id"], 99),
+                ),
+            )],
+        ));
+        // [0, 10): 0 and 5; [10, 100): 10 and 99; 100 and up: 100 and 1000
+        assert_eq!(cardinality_histogram(&plan, &[10, 100]), vec![2, 2, 2]);
+        assert_eq!(cardinality_histogram(&plan, &[]), vec![6]);
+    }
+
+    #[test]
+    fn bytes_scanned_with_a_small_schema() {
+        let plan = parsed(inner_join(
+            &["a.id = b.id"],
+            1,
+            scan("a", &["id", "name"], 10),
+            scan("b", &["id"], 100),
+        ));
+        let widths = HashMap::from([
+            (("a".to_string(), "id".to_string()), 4),
+            (("a".to_string(), "name".to_string()), 20),
+        ]);
+        // b.id isn't in the schema and counts the default width
+        assert_eq!(bytes_scanned(&plan, &widths), 10 * 24 + 100 * 8);
+
+        let huge = parsed(inner_join(
+            &["a.id = b.id"],
+            1,
+            scan("a", &["id"], u64::MAX / 2),
+            scan("b", &["id"], u64::MAX / 2),
+        ));
+        assert_eq!(bytes_scanned(&huge, &widths), u64::MAX);
+    }
+
+    #[test]
+    fn bad_build_choices_flag_larger_build_sides() {
+        let plan = parsed(inner_join(
+            &["b.id = c.id"],
+            1,
+            inner_join(
+                &["a.id = b.id"],
+                10,
+                scan("a", &["id"], 100),
+                scan("b", &["id"], 10),
+            ),
+            scan("c", &["id"], 1000),
+        ));
+        assert_eq!(bad_build_choices(&plan), vec![0]);
+
+        let even = parsed(inner_join(
+            &["a.id = b.id"],
+            1,
+            scan("a", &["id"], 10),
+            scan("b", &["id"], 10),
+        ));
+        assert!(bad_build_choices(&even).is_empty());
+    }
+
+    #[test]
+    fn timing_percentages_sum_to_a_hundred() {
+        let plan = parsed(timed(
+            inner_join(
+                &["a.id = b.id"],
+                1,
+                timed(scan("a", &["id"], 1), 0.3),
+                timed(scan("b", &["id"], 1), 0.1),
+            ),
+            0.6,
+        ));
+        let percentages = timing_percentages(&plan);
+        assert_eq!(percentages.len(), 3);
+        assert!((percentages.values().sum::<f64>() - 100.0).abs() < 1e-9);
+        assert!((percentages[&1] - 30.0).abs() < 1e-9);
+
+        let unmeasured = parsed(timed(scan("a", &["id"], 1), 0.0));
+        assert!(timing_percentages(&unmeasured).is_empty());
+    }
+
+    #[test]
+    fn projected_away_lists_the_dropped_columns() {
+        let plan = parsed(node(
+            "PROJECTION",
+            "a.id\na.y",
+            1,
+            vec![node(
+                "PROJECTION",
+                "a.id\na.x\na.y\na.z",
+                1,
+                vec![scan("a", &["id", "x", "y", "z"], 1)],
+            )],
+        ));
+        let dropped = projected_away(&plan);
+        assert_eq!(
+            dropped[&0],
+            BTreeSet::from([Attribute::new("a", "x"), Attribute::new("a", "z")])
+        );
+        assert!(dropped[&1].is_empty());
+    }
+
+    #[test]
+    fn io_compute_ratio_of_known_timings() {
+        let plan = |join_timing| {
+            parsed(timed(
+                inner_join(
+                    &["a.id = b.id"],
+                    1,
+                    timed(scan("a", &["id"], 1), 0.5),
+                    timed(scan("b", &["id"], 1), 0.25),
+                ),
+                join_timing,
+            ))
+        };
+        assert_eq!(io_compute_ratio(&plan(0.25)), 3.0);
+        assert_eq!(io_compute_ratio(&plan(0.0)), f64::INFINITY);
+        assert_eq!(io_compute_ratio(&parsed(scan("a", &["id"], 1))), 0.0);
+    }
+
+    #[test]
+    fn column_lineage_traces_each_output_column() {
+        let plan = parsed(node(
+            "PROJECTION",
+            "a.x\nb.y",
+            1,
+            vec![inner_join(
+                &["a.id = b.id"],
+                1,
+                node("FILTER", "a.x > 3", 1, vec![scan("a", &["id", "x"], 1)]),
+                scan("b", &["id", "y"], 1),
+            )],
+        ));
+        let lineage = column_lineage(&plan);
+        assert_eq!(lineage.len(), 2);
+        assert_eq!(
+            lineage[&Attribute::new("a", "x")],
+            vec!["SEQ_SCAN", "FILTER", "HASH_JOIN", "PROJECTION"]
+        );
+        assert_eq!(
+            lineage[&Attribute::new("b", "y")],
+            vec!["SEQ_SCAN", "HASH_JOIN", "PROJECTION"]
+        );
+    }
+
+    #[test]
+    fn join_input_modes_of_an_aggregated_build_side() {
+        let plan = parsed(inner_join(
+            &["a.id = b.id"],
+            1,
+            node("FILTER", "a.x > 3", 1, vec![scan("a", &["id", "x"], 1)]),
+            node(
+                "PROJECTION",
+                "b.id",
+                1,
+                vec![node(
+                    "HASH_GROUP_BY",
+                    "b.id",
+                    1,
+                    vec![scan("b", &["id"], 1)],
+                )],
+            ),
+        ));
+        assert_eq!(
+            join_input_modes(&plan),
+            vec![JoinInputMode {
+                node: 0,
+                probe: InputMode::Pipelined,
+                build: InputMode::Materialized,
+            }]
+        );
+    }
+}
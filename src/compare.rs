@@ -0,0 +1,492 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    error::Error,
+    fmt,
+};
+
+use crate::analysis::{base_tables, inner_join_core};
+use crate::export::skeleton;
+use crate::graph::{is_acyclic, JoinGraph};
+use crate::order::cheapest_left_deep_order;
+use crate::{preorder_traverse, Condition, JoinType, Node, NodeId, Op};
+
+fn join_conditions(root: &Node) -> (HashSet<Condition>, bool) {
+    let mut conditions = HashSet::new();
+    let mut only_inner = true;
+    preorder_traverse(root, &mut |node: &Node| match &node.attr {
+        Some(Op::Join(join)) => {
+            only_inner &= join.join_type == JoinType::Inner;
+            conditions.extend(join.equalizers.iter().map(|c| c.canonical()));
+        }
+        Some(Op::MultiwayJoin(multiway)) => {
+            conditions.extend(multiway.conditions.iter().map(|c| c.canonical()));
+        }
+        _ => {}
+    });
+    (conditions, only_inner)
+}
+
+/// Whether two plans join the same base tables on the same conditions,
+/// regardless of join order and tree shape. Plans containing any non-inner
+/// join are only equivalent if they are structurally equal, since those
+/// joins can't be reordered freely.
+pub fn join_equivalent(a: &Node, b: &Node) -> bool {
+    let (a_conditions, a_inner) = join_conditions(a);
+    let (b_conditions, b_inner) = join_conditions(b);
+    if !(a_inner && b_inner) {
+        return a == b;
+    }
+    base_tables(a) == base_tables(b) && a_conditions == b_conditions
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DifferentTables {
+    pub only_in_a: BTreeSet<String>,
+    pub only_in_b: BTreeSet<String>,
+}
+
+impl fmt::Display for DifferentTables {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list =
+            |tables: &BTreeSet<String>| tables.iter().cloned().collect::<Vec<_>>().join(", ");
+        write!(
+            f,
+            "plans scan different tables: [{}] only in the first, [{}] only in the second",
+            list(&self.only_in_a),
+            list(&self.only_in_b)
+        )
+    }
+}
+
+impl Error for DifferentTables {}
+
+// the tables below each join, plus the tables below `node`
+fn join_clusters(node: &Node, clusters: &mut HashSet<BTreeSet<String>>) -> BTreeSet<String> {
+    let mut tables: BTreeSet<String> = match &node.attr {
+        Some(Op::Scan(scan)) => [scan.table_name.clone()].into(),
+        Some(Op::CteScan(cte)) => [cte.name.clone()].into(),
+        _ => BTreeSet::new(),
+    };
+    for child in &node.children {
+        tables.extend(join_clusters(child, clusters));
+    }
+    if matches!(node.attr, Some(Op::Join(_)) | Some(Op::MultiwayJoin(_))) {
+        clusters.insert(tables.clone());
+    }
+    tables
+}
+
+/// The Robinson–Foulds distance between the join trees of two plans over
+/// the same tables: the number of table sets that are joined together
+/// somewhere in one plan but nowhere in the other. Leaves are scanned tables,
+/// so a self-join counts its table once. Sets of one table or of all of them
+/// are in every tree and don't count. Swapping the two inner joins of a
+/// three-table left-deep plan, `(a ⋈ b) ⋈ c` to `(a ⋈ c) ⋈ b`, gives 2.
+pub fn tree_distance(a: &Node, b: &Node) -> Result<usize, DifferentTables> {
+    let (mut a_clusters, mut b_clusters) = (HashSet::new(), HashSet::new());
+    let a_tables = join_clusters(a, &mut a_clusters);
+    let b_tables = join_clusters(b, &mut b_clusters);
+    cluster_distance((a_tables, a_clusters), (b_tables, b_clusters))
+}
+
+/// Like `tree_distance`, with a left-deep join of the tables in `order` as
+/// the second plan.
+pub fn order_distance(root: &Node, order: &[String]) -> Result<usize, DifferentTables> {
+    let mut clusters = HashSet::new();
+    let tables = join_clusters(root, &mut clusters);
+    let order_clusters = (2..=order.len())
+        .map(|n| order[..n].iter().cloned().collect())
+        .collect();
+    let order_tables = order.iter().cloned().collect();
+    cluster_distance((tables, clusters), (order_tables, order_clusters))
+}
+
+// the tables of a join tree and the sets of them it joins together
+type Clusters = (BTreeSet<String>, HashSet<BTreeSet<String>>);
+
+fn cluster_distance(
+    (a_tables, a_clusters): Clusters,
+    (b_tables, b_clusters): Clusters,
+) -> Result<usize, DifferentTables> {
+    if a_tables != b_tables {
+        return Err(DifferentTables {
+            only_in_a: a_tables.difference(&b_tables).cloned().collect(),
+            only_in_b: b_tables.difference(&a_tables).cloned().collect(),
+        });
+    }
+    let nontrivial =
+        |cluster: &&BTreeSet<String>| cluster.len() > 1 && cluster.len() < a_tables.len();
+    Ok(a_clusters
+        .symmetric_difference(&b_clusters)
+        .filter(nontrivial)
+        .count())
+}
+
+// what pins a non-inner join in place: its type and conditions, and the
+// tables of each of its inputs
+type JoinPosition<'a> = (&'a JoinType, BTreeSet<Condition>, Vec<BTreeSet<String>>);
+
+fn non_inner_joins(root: &Node) -> Vec<(NodeId, JoinPosition<'_>)> {
+    fn visit<'a>(node: &'a Node, joins: &mut Vec<(NodeId, JoinPosition<'a>)>) {
+        if let Some(Op::Join(join)) = &node.attr {
+            if join.join_type != JoinType::Inner {
+                let conditions = join.equalizers.iter().map(|c| c.canonical()).collect();
+                let inputs = node.children.iter().map(|c| base_tables(c)).collect();
+                joins.push((node.id, (&join.join_type, conditions, inputs)));
+            }
+        }
+        for child in &node.children {
+            visit(child, joins);
+        }
+    }
+    let mut joins = Vec::new();
+    visit(root, &mut joins);
+    joins
+}
+
+/// Checks a rewrite of `original` for joins moved across an outer (or semi,
+/// anti, mark) join, which can change the result. This is conservative: it
+/// returns the non-inner joins of `rewritten` that don't join exactly the
+/// same tables on each side, with the same type and conditions, as some join
+/// of `original`. Inner joins may be reordered freely within either input,
+/// but swapping the inputs of a non-inner join is flagged even where the
+/// type was flipped to match.
+pub fn check_outer_join_legality(original: &Node, rewritten: &Node) -> Vec<NodeId> {
+    let positions: HashSet<_> = non_inner_joins(original)
+        .into_iter()
+        .map(|(_, position)| position)
+        .collect();
+    non_inner_joins(rewritten)
+        .into_iter()
+        .filter(|(_, position)| !positions.contains(position))
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// One node of several runs of the same plan, with statistics over the runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregatedNode {
+    pub id: NodeId,
+    pub name: String,
+    pub mean_timing: f64,
+    pub stddev_timing: f64,
+    pub mean_card: f64,
+    pub children: Vec<AggregatedNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AggregateError {
+    NoRuns,
+    // the runs differ at this node, e.g. "children[0].children[1]" (empty
+    // for the root)
+    Mismatch { path: String },
+}
+
+impl fmt::Display for AggregateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AggregateError::NoRuns => write!(f, "no runs to aggregate"),
+            AggregateError::Mismatch { path } if path.is_empty() => {
+                write!(f, "runs are not structurally equal at the root")
+            }
+            AggregateError::Mismatch { path } => {
+                write!(f, "runs are not structurally equal at {}", path)
+            }
+        }
+    }
+}
+
+impl Error for AggregateError {}
+
+/// Walks repeated runs of the same plan in lockstep, computing the mean and
+/// (population) standard deviation of each node's timing and its mean
+/// cardinality. Fails if the runs don't have the same operators and shape.
+pub fn aggregate_runs(runs: &[Node]) -> Result<AggregatedNode, AggregateError> {
+    fn aggregate(nodes: &[&Node], path: &str) -> Result<AggregatedNode, AggregateError> {
+        let first = nodes[0];
+        let same_shape = nodes.iter().all(|n| {
+            n.name == first.name && n.attr == first.attr && n.children.len() == first.children.len()
+        });
+        if !same_shape {
+            return Err(AggregateError::Mismatch {
+                path: path.to_string(),
+            });
+        }
+        let count = nodes.len() as f64;
+        let mean_timing = nodes.iter().map(|n| n.timing_or_zero()).sum::<f64>() / count;
+        let variance = nodes
+            .iter()
+            .map(|n| (n.timing_or_zero() - mean_timing).powi(2))
+            .sum::<f64>()
+            / count;
+        let children = (0..first.children.len())
+            .map(|i| {
+                let children: Vec<&Node> = nodes.iter().map(|n| &*n.children[i]).collect();
+                let child_path = if path.is_empty() {
+                    format!("children[{}]", i)
+                } else {
+                    format!("{}.children[{}]", path, i)
+                };
+                aggregate(&children, &child_path)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(AggregatedNode {
+            id: first.id,
+            name: first.name.clone(),
+            mean_timing,
+            stddev_timing: variance.sqrt(),
+            mean_card: nodes.iter().map(|n| n.cardinality as f64).sum::<f64>() / count,
+            children,
+        })
+    }
+
+    if runs.is_empty() {
+        return Err(AggregateError::NoRuns);
+    }
+    let nodes: Vec<&Node> = runs.iter().collect();
+    aggregate(&nodes, "")
+}
+
+/// Statistics over the plans of a workload, see `workload_order_report`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WorkloadReport {
+    pub plans: usize,
+    /// Number of plans with each `skeleton`.
+    pub shapes: BTreeMap<String, usize>,
+    /// Mean number of joins (of any type, including multiway) per plan.
+    pub mean_joins: f64,
+    /// Share of the plans with joins whose join graph has a cycle.
+    pub fraction_cyclic: f64,
+    /// Mean `order_distance` of each plan's inner-join core from the order
+    /// given by `order::cheapest_left_deep_order`, over the plans that have
+    /// one; `None` if none has.
+    pub mean_reference_distance: Option<f64>,
+}
+
+/// Summarizes the join orders DuckDB chose over a workload, e.g. one
+/// benchmark run, taking the cost-based left-deep order of
+/// `order::cheapest_left_deep_order` as the reference planner. Ratios over
+/// no plans are 0.
+pub fn workload_order_report(plans: &[Node]) -> WorkloadReport {
+    let mut shapes = BTreeMap::new();
+    let mut joins = 0;
+    let (mut with_joins, mut cyclic) = (0, 0);
+    let mut distances = Vec::new();
+    for plan in plans {
+        *shapes.entry(skeleton(plan)).or_insert(0) += 1;
+        let mut plan_joins = 0;
+        preorder_traverse(plan, &mut |node: &Node| {
+            if matches!(node.attr, Some(Op::Join(_)) | Some(Op::MultiwayJoin(_))) {
+                plan_joins += 1;
+            }
+        });
+        joins += plan_joins;
+        if plan_joins > 0 {
+            with_joins += 1;
+            if !is_acyclic(&JoinGraph::from_plan(plan)) {
+                cyclic += 1;
+            }
+        }
+        let distance = inner_join_core(plan).and_then(|core| {
+            let order = cheapest_left_deep_order(core)?;
+            order_distance(core, &order).ok()
+        });
+        distances.extend(distance);
+    }
+    let ratio = |n: usize, of: usize| if of == 0 { 0.0 } else { n as f64 / of as f64 };
+    WorkloadReport {
+        plans: plans.len(),
+        shapes,
+        mean_joins: ratio(joins, plans.len()),
+        fraction_cyclic: ratio(cyclic, with_joins),
+        mean_reference_distance: (!distances.is_empty())
+            .then(|| ratio(distances.iter().sum(), distances.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+
+    // (a ⋈ b) ⋈ c
+    fn ab_c() -> Node {
+        parsed(inner_join(
+            &["b.id = c.id"],
+            10,
+            inner_join(
+                &["a.id = b.id"],
+                10,
+                scan("a", &["id"], 10),
+                scan("b", &["id"], 10),
+            ),
+            scan("c", &["id"], 10),
+        ))
+    }
+
+    // (c ⋈ b) ⋈ a, with the conditions written the other way round
+    fn cb_a() -> Node {
+        parsed(inner_join(
+            &["b.id = a.id"],
+            10,
+            inner_join(
+                &["c.id = b.id"],
+                10,
+                scan("c", &["id"], 10),
+                scan("b", &["id"], 10),
+            ),
+            scan("a", &["id"], 10),
+        ))
+    }
+
+    #[test]
+    fn join_orders_are_equivalent() {
+        assert!(join_equivalent(&ab_c(), &cb_a()));
+        let ab = parsed(inner_join(
+            &["a.id = b.id"],
+            10,
+            scan("a", &["id"], 10),
+            scan("b", &["id"], 10),
+        ));
+        assert!(!join_equivalent(&ab_c(), &ab));
+    }
+
+    #[test]
+    fn aggregates_three_runs() {
+        let run = |join_time, scan_time, card| {
+            parsed(timed(
+                inner_join(
+                    &["a.id = b.id"],
+                    card,
+                    timed(scan("a", &["id"], 10), scan_time),
+                    scan("b", &["id"], 10),
+                ),
+                join_time,
+            ))
+        };
+        let runs = [run(1.0, 0.1, 4), run(2.0, 0.1, 5), run(3.0, 0.1, 6)];
+        let aggregated = aggregate_runs(&runs).unwrap();
+        assert_eq!(aggregated.mean_timing, 2.0);
+        assert!((aggregated.stddev_timing - (2.0f64 / 3.0).sqrt()).abs() < 1e-9);
+        assert_eq!(aggregated.mean_card, 5.0);
+        assert!((aggregated.children[0].mean_timing - 0.1).abs() < 1e-9);
+        assert!(aggregated.children[0].stddev_timing < 1e-9);
+        assert_eq!(aggregated.children[1].mean_timing, 0.0);
+
+        let other = parsed(scan("a", &["id"], 10));
+        assert_eq!(
+            aggregate_runs(&[runs[0].clone(), other]),
+            Err(AggregateError::Mismatch {
+                path: String::new()
+            })
+        );
+        assert_eq!(aggregate_runs(&[]), Err(AggregateError::NoRuns));
+    }
+
+    #[test]
+    fn tree_distance_of_swapped_joins() {
+        assert_eq!(tree_distance(&ab_c(), &ab_c()), Ok(0));
+        assert_eq!(tree_distance(&ab_c(), &cb_a()), Ok(2));
+
+        let ab = parsed(inner_join(
+            &["a.id = b.id"],
+            10,
+            scan("a", &["id"], 10),
+            scan("b", &["id"], 10),
+        ));
+        assert_eq!(
+            tree_distance(&ab_c(), &ab),
+            Err(DifferentTables {
+                only_in_a: BTreeSet::from(["c".to_string()]),
+                only_in_b: BTreeSet::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn moving_a_join_into_an_outer_join_is_illegal() {
+        let left_join = |probe: Node, build: Node| {
+            node("HASH_JOIN", "LEFT\na.id = b.id\n", 10, vec![probe, build])
+        };
+        // (a ⟕ b) ⋈ c, which drops the rows of a without a match in b
+        let original = parsed(inner_join(
+            &["b.id = c.id"],
+            10,
+            left_join(scan("a", &["id"], 10), scan("b", &["id"], 10)),
+            scan("c", &["id"], 10),
+        ));
+        // c ⋈ (a ⟕ b) keeps the outer join as it was
+        let swapped = parsed(inner_join(
+            &["c.id = b.id"],
+            10,
+            scan("c", &["id"], 10),
+            left_join(scan("a", &["id"], 10), scan("b", &["id"], 10)),
+        ));
+        assert!(check_outer_join_legality(&original, &swapped).is_empty());
+
+        // a ⟕ (b ⋈ c) keeps them
+        let pushed = parsed(left_join(
+            scan("a", &["id"], 10),
+            inner_join(
+                &["b.id = c.id"],
+                10,
+                scan("b", &["id"], 10),
+                scan("c", &["id"], 10),
+            ),
+        ));
+        assert_eq!(check_outer_join_legality(&original, &pushed), vec![0]);
+    }
+
+    #[test]
+    fn workload_order_report_over_several_plans() {
+        // a ⋈ b keeps all of a, so joining b ⋈ c first would be cheaper
+        let bad = parsed(inner_join(
+            &["b.id = c.id"],
+            100,
+            inner_join(
+                &["a.id = b.id"],
+                1000,
+                scan("a", &["id"], 1000),
+                scan("b", &["id"], 10),
+            ),
+            scan("c", &["id"], 100),
+        ));
+        let good = parsed(inner_join(
+            &["a.id = b.id"],
+            100,
+            inner_join(
+                &["b.id = c.id"],
+                1,
+                scan("b", &["id"], 10),
+                scan("c", &["id"], 100),
+            ),
+            scan("a", &["id"], 1000),
+        ));
+        let cyclic = parsed(inner_join(
+            &["b.y = c.y", "c.z = a.z"],
+            1,
+            inner_join(
+                &["a.x = b.x"],
+                1,
+                scan("a", &["x", "z"], 10),
+                scan("b", &["x", "y"], 10),
+            ),
+            scan("c", &["y", "z"], 10),
+        ));
+        let report = workload_order_report(&[bad, good, cyclic, parsed(scan("a", &["id"], 1))]);
+        assert_eq!(report.plans, 4);
+        assert_eq!(
+            report.shapes,
+            BTreeMap::from([
+                ("HJ(HJ(Scan,Scan),Scan)".to_string(), 3),
+                ("Scan".to_string(), 1)
+            ])
+        );
+        assert_eq!(report.mean_joins, 1.5);
+        assert!((report.fraction_cyclic - 1.0 / 3.0).abs() < 1e-12);
+        assert!((report.mean_reference_distance.unwrap() - 2.0 / 3.0).abs() < 1e-12);
+
+        assert_eq!(workload_order_report(&[]).mean_reference_distance, None);
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use crate::graph::{joined_tables, JoinGraph, RelationId};
+use crate::{preorder_traverse, Condition, JoinType, Node, Op};
+
+/// Selectivity `Selectivities::default` assumes for unknown join conditions.
+pub const DEFAULT_SELECTIVITY: f64 = 0.1;
+
+/// Join selectivities for estimating cardinalities: the known ones by
+/// condition, and the one assumed for every other condition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selectivities {
+    pub learned: HashMap<Condition, f64>,
+    pub default: f64,
+}
+
+impl Default for Selectivities {
+    fn default() -> Self {
+        Selectivities {
+            learned: HashMap::new(),
+            default: DEFAULT_SELECTIVITY,
+        }
+    }
+}
+
+pub fn selectivity(cond: &Condition, selectivities: &Selectivities) -> f64 {
+    selectivities
+        .learned
+        .get(cond)
+        .or_else(|| selectivities.learned.get(&cond.canonical()))
+        .copied()
+        .unwrap_or(selectivities.default)
+}
+
+/// Size of a base relation: the supplied size if any, otherwise the observed
+/// cardinality of its scan, otherwise 1.
+pub fn base_size(graph: &JoinGraph, id: RelationId, base_sizes: &HashMap<String, u64>) -> f64 {
+    let relation = &graph.relations[id.0];
+    base_sizes
+        .get(&relation.name)
+        .copied()
+        .or(relation.cardinality)
+        .unwrap_or(1) as f64
+}
+
+/// Estimated cardinality of joining `relations`: the product of their sizes
+/// times the selectivity of every join condition among them.
+pub fn estimate_cardinality(
+    graph: &JoinGraph,
+    relations: &[RelationId],
+    base_sizes: &HashMap<String, u64>,
+    selectivities: &Selectivities,
+) -> f64 {
+    let sizes: f64 = relations
+        .iter()
+        .map(|&id| base_size(graph, id, base_sizes))
+        .product();
+    let sels: f64 = graph
+        .edges
+        .iter()
+        .filter(|e| relations.contains(&e.left) && relations.contains(&e.right))
+        .map(|e| selectivity(&e.condition, selectivities))
+        .product();
+    sizes * sels
+}
+
+/// Estimated cost of the plan's own join order: the sum of the estimated
+/// output cardinalities of its inner (and multiway) joins.
+pub fn plan_cost(
+    root: &Node,
+    base_sizes: &HashMap<String, u64>,
+    selectivities: &Selectivities,
+) -> f64 {
+    let graph = JoinGraph::from_plan(root);
+    let mut cost = 0.0;
+    preorder_traverse(root, &mut |node: &Node| {
+        let is_inner_join = match &node.attr {
+            Some(Op::Join(join)) => join.join_type == JoinType::Inner,
+            Some(Op::MultiwayJoin(_)) => true,
+            _ => false,
+        };
+        if is_inner_join {
+            let relations: Vec<_> = joined_tables(node)
+                .iter()
+                .filter_map(|t| graph.relation(t))
+                .collect();
+            cost += estimate_cardinality(&graph, &relations, base_sizes, selectivities);
+        }
+    });
+    cost
+}
+
+/// Selectivities of the plan's inner join conditions as observed in the
+/// profile: a join's output cardinality divided by the product of its input
+/// cardinalities. A join with several conditions gives each the same share,
+/// the k-th root of the join's selectivity, so that their product (as used by
+/// `estimate_cardinality`) matches what was observed. Joins with an empty
+/// input tell nothing and are skipped. Conditions are keyed canonically, and
+/// any other condition gets `DEFAULT_SELECTIVITY`.
+pub fn learn_selectivities(root: &Node) -> Selectivities {
+    let mut learned = HashMap::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        let Some(Op::Join(join)) = &node.attr else {
+            return;
+        };
+        if join.join_type != JoinType::Inner || join.equalizers.is_empty() {
+            return;
+        }
+        let inputs: f64 = node.children.iter().map(|c| c.cardinality as f64).product();
+        if node.children.len() != 2 || inputs == 0.0 {
+            return;
+        }
+        let share = (node.cardinality as f64 / inputs).powf(1.0 / join.equalizers.len() as f64);
+        for cond in &join.equalizers {
+            learned.insert(cond.canonical(), share);
+        }
+    });
+    Selectivities {
+        learned,
+        ..Selectivities::default()
+    }
+}
+
+/// A formula for the cost of a subtree, used by `annotate_costs`.
+pub trait CostModel {
+    /// Cost of the subtree rooted at `node`, given the costs of the subtrees
+    /// of its children (in order).
+    fn cost(&self, node: &Node, child_costs: &[f64]) -> f64;
+}
+
+/// Every operator costs the tuples it outputs, plus the cost of its inputs.
+/// A hash join additionally pays for inserting its build side into the hash
+/// table and for probing it with each probe tuple.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultCostModel;
+
+impl CostModel for DefaultCostModel {
+    fn cost(&self, node: &Node, child_costs: &[f64]) -> f64 {
+        let inputs: f64 = child_costs.iter().sum();
+        let own = match (node.name.as_str(), node.children.as_slice()) {
+            ("HASH_JOIN", [probe, build]) => {
+                build.cardinality as f64 + probe.cardinality as f64 + node.cardinality as f64
+            }
+            _ => node.cardinality as f64,
+        };
+        inputs + own
+    }
+}
+
+/// Sets the `cost` of every node to the cost `model` gives its subtree,
+/// computed bottom-up.
+pub fn annotate_costs(root: &mut Node, model: &dyn CostModel) {
+    let child_costs: Vec<f64> = root
+        .children
+        .iter_mut()
+        .map(|child| {
+            annotate_costs(child, model);
+            child.cost.unwrap_or_default()
+        })
+        .collect();
+    root.cost = Some(model.cost(root, &child_costs));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+
+    #[test]
+    fn annotate_costs_sums_bottom_up() {
+        let mut plan = parsed(node(
+            "PROJECTION",
+            "a.id",
+            50,
+            vec![inner_join(
+                &["a.id = b.id"],
+                50,
+                scan("a", &["id"], 100),
+                scan("b", &["id"], 10),
+            )],
+        ));
+        annotate_costs(&mut plan, &DefaultCostModel);
+        let join = &plan.children[0];
+        assert_eq!(join.children[0].cost, Some(100.0));
+        assert_eq!(join.children[1].cost, Some(10.0));
+        // inputs, then building on b, probing with a and the output
+        assert_eq!(join.cost, Some(110.0 + 10.0 + 100.0 + 50.0));
+        assert_eq!(plan.cost, Some(270.0 + 50.0));
+    }
+
+    #[test]
+    fn annotate_costs_uses_the_given_model() {
+        // every node costs 1, so a subtree costs its number of nodes
+        struct NodeCount;
+        impl CostModel for NodeCount {
+            fn cost(&self, _: &Node, child_costs: &[f64]) -> f64 {
+                1.0 + child_costs.iter().sum::<f64>()
+            }
+        }
+        let mut plan = parsed(node(
+            "PROJECTION",
+            "a.id",
+            50,
+            vec![inner_join(
+                &["a.id = b.id"],
+                50,
+                scan("a", &["id"], 100),
+                scan("b", &["id"], 10),
+            )],
+        ));
+        annotate_costs(&mut plan, &NodeCount);
+        let join = &plan.children[0];
+        assert_eq!(join.children[0].cost, Some(1.0));
+        assert_eq!(join.children[1].cost, Some(1.0));
+        assert_eq!(join.cost, Some(3.0));
+        assert_eq!(plan.cost, Some(4.0));
+    }
+
+    #[test]
+    fn hash_join_cost_does_not_overflow() {
+        let mut plan = parsed(inner_join(
+            &["a.id = b.id"],
+            u64::MAX,
+            scan("a", &["id"], u64::MAX),
+            scan("b", &["id"], u64::MAX),
+        ));
+        annotate_costs(&mut plan, &DefaultCostModel);
+        assert!(plan.cost.unwrap() > u64::MAX as f64);
+    }
+
+    #[test]
+    fn learned_selectivities_reproduce_observed_cardinalities() {
+        let plan = parsed(inner_join(
+            &["b.x = c.x", "b.y = c.y"],
+            40,
+            inner_join(
+                &["a.id = b.id"],
+                50,
+                scan("a", &["id"], 100),
+                scan("b", &["id", "x", "y"], 10),
+            ),
+            scan("c", &["x", "y"], 20),
+        ));
+        let learned = learn_selectivities(&plan);
+        assert_eq!(learned.learned.len(), 3);
+        let graph = JoinGraph::from_plan(&plan);
+        let relations: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|t| graph.relation(t).unwrap())
+            .collect();
+        let ab = estimate_cardinality(&graph, &relations[..2], &HashMap::new(), &learned);
+        assert!((ab - 50.0).abs() < 1e-9);
+        let abc = estimate_cardinality(&graph, &relations, &HashMap::new(), &learned);
+        assert!((abc - 40.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn plan_cost_uses_the_default_selectivity() {
+        let plan = parsed(inner_join(
+            &["a.id = b.id"],
+            1,
+            scan("a", &["id"], 100),
+            scan("b", &["id"], 10),
+        ));
+        let sizes = HashMap::new();
+        let cost = plan_cost(&plan, &sizes, &Selectivities::default());
+        assert!((cost - 100.0).abs() < 1e-9);
+        let pessimistic = Selectivities {
+            default: 0.5,
+            ..Selectivities::default()
+        };
+        let cost = plan_cost(&plan, &sizes, &pessimistic);
+        assert!((cost - 500.0).abs() < 1e-9);
+    }
+}
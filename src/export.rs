@@ -0,0 +1,532 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::analysis::operator_root;
+use crate::graph::{JoinEdge, JoinGraph};
+use crate::{JoinType, Node, NodeId, Op, Side};
+
+/// Renders the plan as a relational algebra expression, e.g.
+/// `π_{a.x}(σ_{a.y > 1}((a ⋈_{a.id=b.id} b)))`. Operators without a parsed
+/// `attr` are transparent.
+pub fn to_relational_algebra(root: &Node) -> String {
+    let children: Vec<_> = root
+        .children
+        .iter()
+        .map(|c| to_relational_algebra(c))
+        .collect();
+    match &root.attr {
+        Some(Op::Scan(scan)) => scan.table_name.clone(),
+        Some(Op::CteScan(cte)) => cte.name.clone(),
+        Some(Op::RecursiveCte(cte)) => format!("μ_{{{}}}({})", cte.name, children.join(", ")),
+        Some(Op::Project(project)) => {
+            let columns: Vec<_> = project.columns.iter().map(|c| c.to_string()).collect();
+            format!("π_{{{}}}({})", columns.join(","), children.join(", "))
+        }
+        Some(Op::Filter(filter)) => {
+            let predicates: Vec<_> = filter.predicates.iter().map(|p| p.to_string()).collect();
+            format!("σ_{{{}}}({})", predicates.join(" ∧ "), children.join(", "))
+        }
+        Some(Op::Join(join)) => {
+            let symbol = match (&join.join_type, join.preserved_side) {
+                (JoinType::Inner, _) => "⋈",
+                (JoinType::LeftOuter, _) => "⟕",
+                (JoinType::RightOuter, _) => "⟖",
+                (JoinType::FullOuter, _) => "⟗",
+                (JoinType::Mark, _) => "⋈ᴹ",
+                (JoinType::Semi, Some(Side::Right)) => "⋊",
+                (JoinType::Semi, _) => "⋉",
+                (JoinType::Anti, Some(Side::Right)) => "◁",
+                (JoinType::Anti, _) => "▷",
+            };
+            let conditions: Vec<_> = join
+                .equalizers
+                .iter()
+                .map(|c| format!("{}={}", c.left_attr, c.right_attr))
+                .collect();
+            match children.split_first() {
+                Some((left, rest)) => format!(
+                    "({} {}_{{{}}} {})",
+                    left,
+                    symbol,
+                    conditions.join(","),
+                    rest.join(", ")
+                ),
+                // a join without inputs, as in a truncated profile
+                None => format!("{}_{{{}}}()", symbol, conditions.join(",")),
+            }
+        }
+        Some(Op::MultiwayJoin(multiway)) => {
+            let conditions: Vec<_> = multiway
+                .conditions
+                .iter()
+                .map(|c| format!("{}={}", c.left_attr, c.right_attr))
+                .collect();
+            format!("⋈_{{{}}}({})", conditions.join(","), children.join(", "))
+        }
+        Some(Op::Aggregate(aggregate)) => {
+            let items: Vec<_> = aggregate
+                .groups
+                .iter()
+                .map(|g| g.to_string())
+                .chain(aggregate.aggregates.iter().map(|a| a.to_string()))
+                .collect();
+            format!("γ_{{{}}}({})", items.join(","), children.join(", "))
+        }
+        None => match children.len() {
+            0 => root.name.clone(),
+            1 => children.into_iter().next().unwrap(),
+            _ => format!("{}({})", root.name, children.join(", ")),
+        },
+    }
+}
+
+/// The shape of the plan with operator types only, e.g.
+/// `P(HJ(HJ(Scan,Scan),Scan))`, for grouping structurally identical plans.
+/// Wrappers such as `Query` and `RESULT_COLLECTOR` above the first operator
+/// are left out. Operators are abbreviated as
+///
+/// - `HJ` hash join, `NLJ` nested loop join, `PMJ` piecewise merge join,
+///   `X` cross product, `MJ` multiway join;
+/// - `Scan` sequential scan, `IScan` index scan, `CteScan` CTE scan;
+/// - `F` filter, `P` projection, `Agg` simple aggregate, `GB` (perfect)
+///   hash group by, `Sort` order by, `TopN` top-n;
+///
+/// and anything else keeps its profile name.
+pub fn skeleton(root: &Node) -> String {
+    fn visit(node: &Node, out: &mut String) {
+        out.push_str(match node.name.as_str() {
+            "HASH_JOIN" => "HJ",
+            "NESTED_LOOP_JOIN" => "NLJ",
+            "PIECEWISE_MERGE_JOIN" => "PMJ",
+            "CROSS_PRODUCT" => "X",
+            "MULTIWAY_JOIN" => "MJ",
+            "SEQ_SCAN" => "Scan",
+            "INDEX_SCAN" => "IScan",
+            "CTE_SCAN" => "CteScan",
+            "FILTER" => "F",
+            "PROJECTION" => "P",
+            "SIMPLE_AGGREGATE" => "Agg",
+            "HASH_GROUP_BY" | "PERFECT_HASH_GROUP_BY" => "GB",
+            "ORDER_BY" => "Sort",
+            "TOP_N" => "TopN",
+            name => name,
+        });
+        if !node.children.is_empty() {
+            out.push('(');
+            for (i, child) in node.children.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                visit(child, out);
+            }
+            out.push(')');
+        }
+    }
+    let mut out = String::new();
+    visit(operator_root(root), &mut out);
+    out
+}
+
+// a Newick label, quoted when it contains characters Newick reserves
+fn newick_label(s: &str) -> String {
+    if s.contains(|c: char| c.is_whitespace() || "()[]':;,".contains(c)) {
+        format!("'{}'", s.replace('\'', "''"))
+    } else {
+        s.to_string()
+    }
+}
+
+/// The join tree in Newick format, e.g. `((title,movie_info)Inner,cast)Inner;`:
+/// scanned tables are the leaves, and joins are inner nodes labelled with
+/// their join type (`Multiway` for multiway joins). Other operators are
+/// transparent, except that one with several inputs becomes an inner node
+/// labelled with its name.
+pub fn to_newick(root: &Node) -> String {
+    fn visit(node: &Node) -> String {
+        let label = match &node.attr {
+            Some(Op::Scan(scan)) => return newick_label(&scan.table_name),
+            Some(Op::CteScan(cte)) => return newick_label(&cte.name),
+            Some(Op::Join(join)) => format!("{:?}", join.join_type),
+            Some(Op::MultiwayJoin(_)) => "Multiway".to_string(),
+            _ => newick_label(&node.name),
+        };
+        let children: Vec<_> = node.children.iter().map(|c| visit(c)).collect();
+        match children.len() {
+            0 => label,
+            1 if !matches!(node.attr, Some(Op::Join(_)) | Some(Op::MultiwayJoin(_))) => {
+                children.into_iter().next().unwrap()
+            }
+            _ => format!("({}){}", children.join(","), label),
+        }
+    }
+    format!("{};", visit(root))
+}
+
+/// A node of the plan with what a tree renderer needs to draw its branch.
+#[derive(Debug, Clone)]
+pub struct RenderItem<'a> {
+    pub node: &'a Node,
+    pub depth: usize,
+    // whether the node is the last child of its parent (true for the root)
+    pub is_last: bool,
+    // for each ancestor below the root, outermost first, whether it is the
+    // last child of its parent, i.e. whether its branch line has ended
+    pub ancestors_last: Vec<bool>,
+}
+
+/// The nodes of the plan in preorder, for drawing it as a tree: below the
+/// root, a node gets `└─` if it `is_last` and `├─` otherwise, after one
+/// column per entry of `ancestors_last` that is blank if true and `│` if not.
+pub fn render_iter(root: &Node) -> impl Iterator<Item = RenderItem<'_>> {
+    let mut stack = vec![RenderItem {
+        node: root,
+        depth: 0,
+        is_last: true,
+        ancestors_last: Vec::new(),
+    }];
+    std::iter::from_fn(move || {
+        let item = stack.pop()?;
+        let mut ancestors_last = item.ancestors_last.clone();
+        if item.depth > 0 {
+            ancestors_last.push(item.is_last);
+        }
+        let n = item.node.children.len();
+        // pushed in reverse so that children come out left to right
+        for (i, child) in item.node.children.iter().enumerate().rev() {
+            stack.push(RenderItem {
+                node: child,
+                depth: item.depth + 1,
+                is_last: i == n - 1,
+                ancestors_last: ancestors_last.clone(),
+            });
+        }
+        Some(item)
+    })
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Writes one CSV row per node, in preorder, for loading into a spreadsheet.
+pub fn write_node_csv(root: &Node, mut w: impl Write) -> io::Result<()> {
+    fn write_rows(
+        node: &Node,
+        parent: Option<NodeId>,
+        depth: usize,
+        w: &mut impl Write,
+    ) -> io::Result<()> {
+        writeln!(
+            w,
+            "{},{},{},{},{},{},{}",
+            node.id,
+            parent.map(|p| p.to_string()).unwrap_or_default(),
+            depth,
+            csv_field(&node.name),
+            csv_field(&node.summary()),
+            node.cardinality,
+            node.timing.map(|t| t.to_string()).unwrap_or_default()
+        )?;
+        for child in &node.children {
+            write_rows(child, Some(node.id), depth + 1, w)?;
+        }
+        Ok(())
+    }
+
+    writeln!(
+        w,
+        "id,parent_id,depth,operator_type,table_or_summary,cardinality,timing"
+    )?;
+    write_rows(root, None, 0, &mut w)
+}
+
+// the fields of a node that DuckDB itself writes
+#[derive(Serialize)]
+struct ProfileNode<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timing: Option<f64>,
+    cardinality: u64,
+    extra_info: &'a str,
+    children: Vec<ProfileNode<'a>>,
+}
+
+impl<'a> From<&'a Node> for ProfileNode<'a> {
+    fn from(node: &'a Node) -> Self {
+        ProfileNode {
+            name: &node.name,
+            timing: node.timing,
+            cardinality: node.cardinality,
+            extra_info: &node.extra_info,
+            children: node.children.iter().map(|c| c.as_ref().into()).collect(),
+        }
+    }
+}
+
+/// The plan as a DuckDB profile, with only the fields DuckDB writes (timings
+/// in seconds), so that profile viewers can read processed trees. The
+/// synthetic `id`, `cost` and `attr` are dropped; loading the output with
+/// `from_str` parses `attr` again from `extra_info`.
+pub fn to_duckdb_profile_json(root: &Node) -> String {
+    serde_json::to_string(&ProfileNode::from(root)).unwrap()
+}
+
+// edges ordered by their (canonical) condition, independent of plan shape
+fn sorted_edges(graph: &JoinGraph) -> Vec<&JoinEdge> {
+    let mut edges: Vec<_> = graph.edges.iter().collect();
+    edges.sort_by(|a, b| a.condition.cmp(&b.condition));
+    edges
+}
+
+/// The join graph as a tab-separated edge list, one `left right label` line
+/// per join condition, e.g. for networkx's
+/// `read_edgelist(path, delimiter="\t", data=[("label", str)])`.
+pub fn join_graph_to_edge_list(graph: &JoinGraph) -> String {
+    sorted_edges(graph)
+        .into_iter()
+        .map(|e| {
+            format!(
+                "{}\t{}\t{}\n",
+                graph.name(e.left),
+                graph.name(e.right),
+                e.condition
+            )
+        })
+        .collect()
+}
+
+/// Writes the join graph as JSON. Relations are listed in id order with
+/// their table names, and edges refer to them by id, so the graph reads back
+/// equal with `serde_json::from_reader`.
+pub fn write_join_graph(graph: &JoinGraph, w: impl Write) -> io::Result<()> {
+    serde_json::to_writer_pretty(w, graph)?;
+    Ok(())
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The join graph as GraphML: tables as nodes (with their scan cardinality
+/// when known), join conditions as undirected edges labelled with the
+/// attribute pair.
+pub fn join_graph_to_graphml(graph: &JoinGraph) -> String {
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"cardinality\" for=\"node\" attr.name=\"cardinality\" attr.type=\"long\"/>\n",
+        "  <key id=\"label\" for=\"edge\" attr.name=\"label\" attr.type=\"string\"/>\n",
+        "  <graph id=\"join_graph\" edgedefault=\"undirected\">\n",
+    ));
+    for relation in &graph.relations {
+        let name = xml_escape(&relation.name);
+        match relation.cardinality {
+            Some(card) => out.push_str(&format!(
+                "    <node id=\"{}\"><data key=\"cardinality\">{}</data></node>\n",
+                name, card
+            )),
+            None => out.push_str(&format!("    <node id=\"{}\"/>\n", name)),
+        }
+    }
+    for edge in sorted_edges(graph) {
+        out.push_str(&format!(
+            "    <edge source=\"{}\" target=\"{}\"><data key=\"label\">{}</data></edge>\n",
+            xml_escape(graph.name(edge.left)),
+            xml_escape(graph.name(edge.right)),
+            xml_escape(&edge.condition.to_string())
+        ));
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+
+    #[test]
+    fn relational_algebra_of_project_filter_join() {
+        let plan = parsed(node(
+            "PROJECTION",
+            "a.x",
+            1,
+            vec![node(
+                "FILTER",
+                "a.y > 1",
+                1,
+                vec![inner_join(
+                    &["a.id = b.id"],
+                    1,
+                    scan("a", &["id", "x", "y"], 1),
+                    scan("b", &["id"], 1),
+                )],
+            )],
+        ));
+        assert_eq!(
+            to_relational_algebra(&plan),
+            "π_{a.x}(σ_{a.y > 1}((a ⋈_{a.id=b.id} b)))"
+        );
+    }
+
+    #[test]
+    fn relational_algebra_of_join_without_children() {
+        let plan = parsed(node("HASH_JOIN", "INNER\na.id = b.id\n", 1, vec![]));
+        assert_eq!(to_relational_algebra(&plan), "⋈_{a.id=b.id}()");
+    }
+
+    #[test]
+    fn node_csv_has_a_header_and_a_row_per_node() {
+        let plan = parsed(inner_join(
+            &["a.id = b.id"],
+            5,
+            timed(scan("a", &["id"], 10), 0.5),
+            scan("b", &["id"], 20),
+        ));
+        let mut out = Vec::new();
+        write_node_csv(&plan, &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "id,parent_id,depth,operator_type,table_or_summary,cardinality,timing"
+        );
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("0,,0,HASH_JOIN,"));
+        assert!(lines[2].starts_with("1,0,1,SEQ_SCAN,"));
+        assert!(lines[2].ends_with(",10,0.5"));
+        assert!(lines[3].ends_with(",20,"));
+    }
+
+    #[test]
+    fn edge_list_has_one_line_per_distinct_condition() {
+        // a.id = b.id appears twice, the second time reversed
+        let plan = parsed(inner_join(
+            &["b.id = a.id", "b.x = c.x"],
+            1,
+            inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id"], 1),
+                scan("b", &["id", "x"], 1),
+            ),
+            scan("c", &["x"], 1),
+        ));
+        let graph = JoinGraph::from_plan(&plan);
+        let edges = join_graph_to_edge_list(&graph);
+        assert_eq!(edges.lines().count(), 2);
+        assert_eq!(edges.lines().count(), graph.edges.len());
+        assert!(edges.lines().all(|l| l.split('\t').count() == 3));
+    }
+
+    #[test]
+    fn join_graph_round_trips_through_json() {
+        let plan = parsed(inner_join(
+            &["b.id = c.id"],
+            1,
+            inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id"], 100),
+                scan("b", &["id"], 10),
+            ),
+            scan("c", &["id"], 1),
+        ));
+        let graph = JoinGraph::from_plan(&plan);
+        let mut out = Vec::new();
+        write_join_graph(&graph, &mut out).unwrap();
+        let read: JoinGraph = serde_json::from_reader(out.as_slice()).unwrap();
+        assert_eq!(read, graph);
+        assert_eq!(read.len(), 3);
+    }
+
+    #[test]
+    fn duckdb_profile_json_reads_back_equal() {
+        let original = crate::from_str(include_str!("../tests/data/in_subquery.json")).unwrap();
+        let exported = to_duckdb_profile_json(&original);
+        let read = crate::from_str(&exported).unwrap();
+        assert_trees_eq(&original, &read);
+        assert_eq!(
+            read.find(4).unwrap().timing,
+            original.find(4).unwrap().timing
+        );
+        assert!(!exported.contains("\"attr\""));
+    }
+
+    #[test]
+    fn skeleton_of_a_small_plan() {
+        let plan = parsed(node(
+            "HASH_GROUP_BY",
+            "#0\ncount_star()",
+            1,
+            vec![inner_join(
+                &["a.id = b.id"],
+                1,
+                node("FILTER", "a.x > 3", 1, vec![scan("a", &["id", "x"], 1)]),
+                node("INDEX_SCAN", "b\n[INFOSEPARATOR]\nid", 1, vec![]),
+            )],
+        ));
+        assert_eq!(skeleton(&plan), "GB(HJ(F(Scan),IScan))");
+    }
+
+    #[test]
+    fn newick_of_three_joins() {
+        let left_join = node(
+            "HASH_JOIN",
+            "LEFT\na.id = b.id\n",
+            1,
+            vec![
+                node("FILTER", "a.x > 3", 1, vec![scan("a", &["id", "x"], 1)]),
+                scan("b", &["id"], 1),
+            ],
+        );
+        let plan = parsed(node(
+            "PROJECTION",
+            "a.id",
+            1,
+            vec![inner_join(
+                &["c.id = d.id"],
+                1,
+                inner_join(&["b.id = c.id"], 1, left_join, scan("c", &["id"], 1)),
+                scan("d", &["id"], 1),
+            )],
+        ));
+        assert_eq!(to_newick(&plan), "(((a,b)LeftOuter,c)Inner,d)Inner;");
+    }
+
+    #[test]
+    fn render_iter_flags_last_children_and_open_branches() {
+        let plan = parsed(inner_join(
+            &["b.id = c.id"],
+            1,
+            inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id"], 1),
+                scan("b", &["id"], 1),
+            ),
+            scan("c", &["id"], 1),
+        ));
+        let items: Vec<_> = render_iter(&plan)
+            .map(|item| (item.node.id, item.depth, item.is_last, item.ancestors_last))
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                (0, 0, true, vec![]),
+                (1, 1, false, vec![]),
+                (2, 2, false, vec![false]),
+                (3, 2, true, vec![false]),
+                (4, 1, true, vec![]),
+            ]
+        );
+    }
+}
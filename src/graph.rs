@@ -0,0 +1,685 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{preorder_traverse, Attribute, Condition, JoinType, Node, Op};
+
+/// Index of a relation in `JoinGraph::relations`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RelationId(pub usize);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Relation {
+    pub name: String,
+    // observed output cardinality of the scan of this table, if the plan has one
+    pub cardinality: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct JoinEdge {
+    pub left: RelationId,
+    pub right: RelationId,
+    pub condition: Condition,
+}
+
+/// The tables a plan scans or joins, with one edge per distinct
+/// (canonicalized) equi-join condition of its inner joins.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct JoinGraph {
+    pub relations: Vec<Relation>,
+    pub edges: Vec<JoinEdge>,
+}
+
+impl JoinGraph {
+    pub fn from_plan(root: &Node) -> JoinGraph {
+        let mut conditions: Vec<Condition> = Vec::new();
+        let mut scan_cards = BTreeMap::new();
+        preorder_traverse(root, &mut |node: &Node| match &node.attr {
+            Some(Op::Scan(scan)) => {
+                scan_cards
+                    .entry(scan.table_name.clone())
+                    .or_insert(node.cardinality);
+            }
+            Some(Op::CteScan(cte)) => {
+                scan_cards
+                    .entry(cte.name.clone())
+                    .or_insert(node.cardinality);
+            }
+            Some(op) => {
+                for cond in op.inner_join_conditions() {
+                    let cond = cond.canonical();
+                    if !conditions.contains(&cond) {
+                        conditions.push(cond);
+                    }
+                }
+            }
+            None => {}
+        });
+
+        // relations are numbered in name order so that ids are stable
+        // across plans joining the same tables. Every scanned table is one,
+        // even if no condition joins it (as under a cross product).
+        let names: BTreeSet<&str> = conditions
+            .iter()
+            .flat_map(|c| {
+                [
+                    c.left_attr.table_name.as_str(),
+                    c.right_attr.table_name.as_str(),
+                ]
+            })
+            .chain(scan_cards.keys().map(String::as_str))
+            .collect();
+        let relations: Vec<_> = names
+            .iter()
+            .map(|name| Relation {
+                name: name.to_string(),
+                cardinality: scan_cards.get(*name).copied(),
+            })
+            .collect();
+
+        let mut graph = JoinGraph {
+            relations,
+            edges: Vec::new(),
+        };
+        for condition in conditions {
+            let left = graph.relation(&condition.left_attr.table_name).unwrap();
+            let right = graph.relation(&condition.right_attr.table_name).unwrap();
+            graph.edges.push(JoinEdge {
+                left,
+                right,
+                condition,
+            });
+        }
+        graph
+    }
+
+    pub fn relation(&self, name: &str) -> Option<RelationId> {
+        self.relations
+            .iter()
+            .position(|r| r.name == name)
+            .map(RelationId)
+    }
+
+    pub fn name(&self, id: RelationId) -> &str {
+        &self.relations[id.0].name
+    }
+
+    pub fn len(&self) -> usize {
+        self.relations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.relations.is_empty()
+    }
+
+    /// Relations sharing at least one join condition with `id`, in id order.
+    pub fn neighbors(&self, id: RelationId) -> Vec<RelationId> {
+        let neighbors: BTreeSet<_> = self
+            .edges
+            .iter()
+            .filter_map(|e| {
+                if e.left == id && e.right != id {
+                    Some(e.right)
+                } else if e.right == id && e.left != id {
+                    Some(e.left)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        neighbors.into_iter().collect()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let mut seen = vec![false; self.len()];
+        let mut stack = vec![RelationId(0)];
+        seen[0] = true;
+        while let Some(id) = stack.pop() {
+            for n in self.neighbors(id) {
+                if !seen[n.0] {
+                    seen[n.0] = true;
+                    stack.push(n);
+                }
+            }
+        }
+        seen.into_iter().all(|s| s)
+    }
+
+    /// Class index of every join attribute, where attributes transitively
+    /// equated by join conditions share a class. Classes are numbered in
+    /// order of first appearance in `edges`.
+    pub fn attribute_classes(&self) -> BTreeMap<Attribute, usize> {
+        let mut attrs: Vec<&Attribute> = Vec::new();
+        for e in &self.edges {
+            for a in [&e.condition.left_attr, &e.condition.right_attr] {
+                if !attrs.contains(&a) {
+                    attrs.push(a);
+                }
+            }
+        }
+        let index = |a: &Attribute| attrs.iter().position(|b| *b == a).unwrap();
+        let mut parent: Vec<usize> = (0..attrs.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        for e in &self.edges {
+            let l = root(&mut parent, index(&e.condition.left_attr));
+            let r = root(&mut parent, index(&e.condition.right_attr));
+            parent[l.max(r)] = l.min(r);
+        }
+        let mut class_of_root = BTreeMap::new();
+        let mut classes = BTreeMap::new();
+        for (i, a) in attrs.iter().enumerate() {
+            let r = root(&mut parent, i);
+            let next = class_of_root.len();
+            let class = *class_of_root.entry(r).or_insert(next);
+            classes.insert((*a).clone(), class);
+        }
+        classes
+    }
+
+    /// The query hypergraph: one hyperedge per relation, over the attribute
+    /// classes of its join attributes.
+    pub fn hypergraph(&self) -> Vec<Hyperedge> {
+        let classes = self.attribute_classes();
+        let mut edges: Vec<_> = (0..self.len())
+            .map(|i| Hyperedge {
+                relation: RelationId(i),
+                vertices: BTreeSet::new(),
+            })
+            .collect();
+        for e in &self.edges {
+            for (id, a) in [
+                (e.left, &e.condition.left_attr),
+                (e.right, &e.condition.right_attr),
+            ] {
+                edges[id.0].vertices.insert(classes[a]);
+            }
+        }
+        edges
+    }
+
+    /// Upper bound on the treewidth of the join graph from the min-degree
+    /// elimination heuristic.
+    pub fn treewidth_upper_bound(&self) -> usize {
+        let mut adjacency: Vec<BTreeSet<usize>> = (0..self.len())
+            .map(|i| {
+                self.neighbors(RelationId(i))
+                    .into_iter()
+                    .map(|n| n.0)
+                    .collect()
+            })
+            .collect();
+        let mut remaining: BTreeSet<usize> = (0..self.len()).collect();
+        let mut width = 0;
+        while let Some(&v) = remaining.iter().min_by_key(|&&v| adjacency[v].len()) {
+            let neighbors: Vec<_> = adjacency[v].iter().copied().collect();
+            width = width.max(neighbors.len());
+            for &a in &neighbors {
+                adjacency[a].remove(&v);
+                for &b in &neighbors {
+                    if a != b {
+                        adjacency[a].insert(b);
+                    }
+                }
+            }
+            remaining.remove(&v);
+        }
+        width
+    }
+
+    /// Maximal sets of pairwise-joined relations (Bron–Kerbosch with pivoting).
+    pub fn maximal_cliques(&self) -> Vec<Vec<RelationId>> {
+        fn expand(
+            graph: &JoinGraph,
+            r: Vec<RelationId>,
+            mut p: BTreeSet<RelationId>,
+            mut x: BTreeSet<RelationId>,
+            cliques: &mut Vec<Vec<RelationId>>,
+        ) {
+            if p.is_empty() && x.is_empty() {
+                cliques.push(r);
+                return;
+            }
+            let pivot = *p.iter().chain(x.iter()).next().unwrap();
+            let pivot_neighbors = graph.neighbors(pivot);
+            let candidates: Vec<_> = p
+                .iter()
+                .copied()
+                .filter(|v| !pivot_neighbors.contains(v))
+                .collect();
+            for v in candidates {
+                let neighbors: BTreeSet<_> = graph.neighbors(v).into_iter().collect();
+                let mut clique = r.clone();
+                clique.push(v);
+                expand(
+                    graph,
+                    clique,
+                    p.intersection(&neighbors).copied().collect(),
+                    x.intersection(&neighbors).copied().collect(),
+                    cliques,
+                );
+                p.remove(&v);
+                x.insert(v);
+            }
+        }
+
+        let mut cliques = Vec::new();
+        let all = (0..self.len()).map(RelationId).collect();
+        expand(self, Vec::new(), all, BTreeSet::new(), &mut cliques);
+        for clique in &mut cliques {
+            clique.sort();
+        }
+        cliques.sort();
+        cliques
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Hyperedge {
+    pub relation: RelationId,
+    pub vertices: BTreeSet<usize>,
+}
+
+// GYO reduction: repeatedly drop vertices that occur in a single hyperedge
+// and hyperedges contained in another one. Returns the hyperedges left over,
+// which are at most one exactly when the hypergraph is acyclic, and each
+// dropped hyperedge paired with the one containing it (its join tree parent).
+fn gyo_reduce(mut edges: Vec<Hyperedge>) -> (Vec<Hyperedge>, Vec<(RelationId, RelationId)>) {
+    let mut join_tree = Vec::new();
+    loop {
+        let mut changed = false;
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for e in &edges {
+            for v in &e.vertices {
+                *counts.entry(*v).or_insert(0) += 1;
+            }
+        }
+        for e in &mut edges {
+            let before = e.vertices.len();
+            e.vertices.retain(|v| counts[v] > 1);
+            changed |= e.vertices.len() != before;
+        }
+        let contained = (0..edges.len()).find_map(|i| {
+            (0..edges.len())
+                .find(|&j| {
+                    i != j
+                        && edges[i].vertices.is_subset(&edges[j].vertices)
+                        // of two equal hyperedges only drop the first
+                        && (edges[i].vertices != edges[j].vertices || i < j)
+                })
+                .map(|j| (i, j))
+        });
+        if let Some((i, j)) = contained {
+            join_tree.push((edges[i].relation, edges[j].relation));
+            edges.remove(i);
+            changed = true;
+        }
+        if !changed {
+            return (edges, join_tree);
+        }
+    }
+}
+
+/// Whether the query hypergraph is alpha-acyclic, i.e. has a join tree.
+pub fn is_acyclic(graph: &JoinGraph) -> bool {
+    gyo_reduce(graph.hypergraph()).0.len() <= 1
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AcyclicityReport {
+    // join tree edges as (child, parent) pairs
+    Acyclic {
+        join_tree: Vec<(RelationId, RelationId)>,
+    },
+    // the hyperedges GYO reduction could not remove, i.e. the cyclic core
+    Cyclic {
+        residual: Vec<Hyperedge>,
+    },
+}
+
+/// Explains the result of `is_acyclic`: the join tree found by GYO
+/// reduction, or the hyperedges left over when reduction got stuck.
+pub fn acyclicity_report(graph: &JoinGraph) -> AcyclicityReport {
+    let (residual, join_tree) = gyo_reduce(graph.hypergraph());
+    if residual.len() <= 1 {
+        AcyclicityReport::Acyclic { join_tree }
+    } else {
+        AcyclicityReport::Cyclic { residual }
+    }
+}
+
+// eccentricity of every relation: its largest hop distance to another
+// relation; None if the graph is empty or disconnected
+fn eccentricities(graph: &JoinGraph) -> Option<Vec<usize>> {
+    if graph.is_empty() || !graph.is_connected() {
+        return None;
+    }
+    let neighbors: Vec<_> = (0..graph.len())
+        .map(|i| graph.neighbors(RelationId(i)))
+        .collect();
+    let eccentricity = |start: usize| {
+        let mut distance = vec![usize::MAX; graph.len()];
+        distance[start] = 0;
+        let mut queue = VecDeque::from([start]);
+        while let Some(i) = queue.pop_front() {
+            for n in &neighbors[i] {
+                if distance[n.0] == usize::MAX {
+                    distance[n.0] = distance[i] + 1;
+                    queue.push_back(n.0);
+                }
+            }
+        }
+        distance.into_iter().max().unwrap()
+    };
+    Some((0..graph.len()).map(eccentricity).collect())
+}
+
+/// Longest shortest path, in joins, between two relations: n - 1 for a chain
+/// of n relations, 2 for a star. `None` if the graph is disconnected.
+pub fn graph_diameter(graph: &JoinGraph) -> Option<usize> {
+    eccentricities(graph)?.into_iter().max()
+}
+
+/// Smallest, over all relations, of the longest shortest path to another
+/// relation: 1 for a star, whose center reaches everything in one join.
+/// `None` if the graph is disconnected.
+pub fn graph_radius(graph: &JoinGraph) -> Option<usize> {
+    eccentricities(graph)?.into_iter().min()
+}
+
+/// Sets of three or more relations that are all joined with each other. A
+/// worst-case optimal multiway join over such a set can beat any binary plan.
+pub fn multiway_join_candidates(root: &Node) -> Vec<BTreeSet<String>> {
+    let graph = JoinGraph::from_plan(root);
+    graph
+        .maximal_cliques()
+        .into_iter()
+        .filter(|clique| clique.len() >= 3)
+        .map(|clique| {
+            clique
+                .into_iter()
+                .map(|id| graph.name(id).to_string())
+                .collect()
+        })
+        .collect()
+}
+
+/// The observed selectivity of the joins between each pair of tables: a
+/// binary inner join's output cardinality divided by the product of its
+/// input cardinalities, given to every table pair it has a condition between.
+/// Pairs are keyed in name order. Where several joins connect the same pair
+/// (e.g. a repeated table), the smallest selectivity is kept, as the most
+/// selective way of joining them. Joins with an empty input are skipped.
+pub fn weighted_join_graph(root: &Node) -> HashMap<(String, String), f64> {
+    let mut weights: HashMap<(String, String), f64> = HashMap::new();
+    preorder_traverse(root, &mut |node: &Node| {
+        let Some(Op::Join(join)) = &node.attr else {
+            return;
+        };
+        let inputs: f64 = node.children.iter().map(|c| c.cardinality as f64).product();
+        if join.join_type != JoinType::Inner || node.children.len() != 2 || inputs == 0.0 {
+            return;
+        }
+        let selectivity = node.cardinality as f64 / inputs;
+        for cond in &join.equalizers {
+            let (left, right) = (&cond.left_attr.table_name, &cond.right_attr.table_name);
+            let pair = if left <= right {
+                (left.clone(), right.clone())
+            } else {
+                (right.clone(), left.clone())
+            };
+            let weight = weights.entry(pair).or_insert(selectivity);
+            *weight = weight.min(selectivity);
+        }
+    });
+    weights
+}
+
+/// Tables referenced by the inner-join conditions in the subtree rooted at
+/// `node`, i.e. the relations that subtree has joined together.
+pub fn joined_tables(node: &Node) -> BTreeSet<String> {
+    let mut tables = BTreeSet::new();
+    preorder_traverse(node, &mut |n: &Node| {
+        if let Some(op) = &n.attr {
+            for cond in op.inner_join_conditions() {
+                tables.insert(cond.left_attr.table_name.clone());
+                tables.insert(cond.right_attr.table_name.clone());
+            }
+        }
+    });
+    tables
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RelationRole {
+    Fact,
+    Dimension,
+    Bridge,
+}
+
+/// Thresholds for `classify_relations_with`.
+#[derive(Debug, Clone)]
+pub struct RoleThresholds {
+    // a relation joined with at least this many others is a fact table (3)
+    pub fact_min_degree: usize,
+    // a relation at least this fraction of the largest relation's
+    // cardinality is a fact table (0.5)
+    pub fact_min_card_fraction: f64,
+    // any other relation joined with at most this many others is a
+    // dimension table (1); the rest are bridges
+    pub dimension_max_degree: usize,
+}
+
+impl Default for RoleThresholds {
+    fn default() -> Self {
+        RoleThresholds {
+            fact_min_degree: 3,
+            fact_min_card_fraction: 0.5,
+            dimension_max_degree: 1,
+        }
+    }
+}
+
+/// Labels each relation with its star-schema role using the default
+/// `RoleThresholds`.
+pub fn classify_relations(graph: &JoinGraph) -> BTreeMap<RelationId, RelationRole> {
+    classify_relations_with(graph, &RoleThresholds::default())
+}
+
+pub fn classify_relations_with(
+    graph: &JoinGraph,
+    thresholds: &RoleThresholds,
+) -> BTreeMap<RelationId, RelationRole> {
+    let max_card = graph
+        .relations
+        .iter()
+        .filter_map(|r| r.cardinality)
+        .max()
+        .unwrap_or(0);
+    (0..graph.len())
+        .map(RelationId)
+        .map(|id| {
+            let degree = graph.neighbors(id).len();
+            let large = graph.relations[id.0].cardinality.is_some_and(|c| {
+                max_card > 0 && c as f64 >= thresholds.fact_min_card_fraction * max_card as f64
+            });
+            let role = if degree >= thresholds.fact_min_degree || large {
+                RelationRole::Fact
+            } else if degree <= thresholds.dimension_max_degree {
+                RelationRole::Dimension
+            } else {
+                RelationRole::Bridge
+            };
+            (id, role)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+
+    // a, b and c all joined with each other on different columns
+    fn triangle() -> Node {
+        parsed(inner_join(
+            &["b.y = c.y", "c.z = a.z"],
+            1,
+            inner_join(
+                &["a.x = b.x"],
+                1,
+                scan("a", &["x", "z"], 1),
+                scan("b", &["x", "y"], 1),
+            ),
+            scan("c", &["y", "z"], 1),
+        ))
+    }
+
+    #[test]
+    fn triangle_is_a_multiway_candidate() {
+        let expected: BTreeSet<String> = ["a", "b", "c"].map(String::from).into();
+        assert_eq!(multiway_join_candidates(&triangle()), vec![expected]);
+
+        let chain = parsed(inner_join(
+            &["b.y = c.y"],
+            1,
+            inner_join(
+                &["a.x = b.x"],
+                1,
+                scan("a", &["x"], 1),
+                scan("b", &["x", "y"], 1),
+            ),
+            scan("c", &["y"], 1),
+        ));
+        assert!(multiway_join_candidates(&chain).is_empty());
+    }
+
+    // a fact table f joined with three dimensions
+    fn star() -> Node {
+        parsed(inner_join(
+            &["f.d3 = d3.id"],
+            1000,
+            inner_join(
+                &["f.d2 = d2.id"],
+                1000,
+                inner_join(
+                    &["f.d1 = d1.id"],
+                    1000,
+                    scan("f", &["d1", "d2", "d3"], 1000),
+                    scan("d1", &["id"], 10),
+                ),
+                scan("d2", &["id"], 10),
+            ),
+            scan("d3", &["id"], 10),
+        ))
+    }
+
+    #[test]
+    fn classifies_a_star() {
+        let graph = JoinGraph::from_plan(&star());
+        let roles: BTreeMap<_, _> = classify_relations(&graph)
+            .into_iter()
+            .map(|(id, role)| (graph.name(id).to_string(), role))
+            .collect();
+        assert_eq!(roles["f"], RelationRole::Fact);
+        for dimension in ["d1", "d2", "d3"] {
+            assert_eq!(roles[dimension], RelationRole::Dimension);
+        }
+    }
+
+    #[test]
+    fn triangle_leaves_a_cyclic_core() {
+        let graph = JoinGraph::from_plan(&triangle());
+        let AcyclicityReport::Cyclic { residual } = acyclicity_report(&graph) else {
+            panic!("a triangle is cyclic");
+        };
+        let relations: BTreeSet<_> = residual.iter().map(|e| graph.name(e.relation)).collect();
+        assert_eq!(relations, ["a", "b", "c"].into());
+        assert!(residual.iter().all(|e| e.vertices.len() == 2));
+
+        let AcyclicityReport::Acyclic { join_tree } =
+            acyclicity_report(&JoinGraph::from_plan(&star()))
+        else {
+            panic!("a star is acyclic");
+        };
+        assert_eq!(join_tree.len(), 3);
+    }
+
+    #[test]
+    fn diameter_and_radius_of_a_chain_and_a_star() {
+        // a - b - c - d
+        let chain = parsed(inner_join(
+            &["c.id = d.id"],
+            1,
+            inner_join(
+                &["b.id = c.id"],
+                1,
+                inner_join(
+                    &["a.id = b.id"],
+                    1,
+                    scan("a", &["id"], 1),
+                    scan("b", &["id"], 1),
+                ),
+                scan("c", &["id"], 1),
+            ),
+            scan("d", &["id"], 1),
+        ));
+        let graph = JoinGraph::from_plan(&chain);
+        assert_eq!(graph_diameter(&graph), Some(3));
+        assert_eq!(graph_radius(&graph), Some(2));
+
+        let graph = JoinGraph::from_plan(&star());
+        assert_eq!(graph_diameter(&graph), Some(2));
+        assert_eq!(graph_radius(&graph), Some(1));
+
+        // (a - b) x c
+        let cross = parsed(node(
+            "HASH_JOIN",
+            "INNER\n",
+            1,
+            vec![
+                inner_join(
+                    &["a.id = b.id"],
+                    1,
+                    scan("a", &["id"], 1),
+                    scan("b", &["id"], 1),
+                ),
+                scan("c", &["id"], 1),
+            ],
+        ));
+        let graph = JoinGraph::from_plan(&cross);
+        assert_eq!(graph.len(), 3);
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!(graph_diameter(&graph), None);
+        assert_eq!(graph_radius(&graph), None);
+    }
+
+    #[test]
+    fn weighted_join_graph_keeps_the_smallest_selectivity() {
+        let plan = parsed(inner_join(
+            &["c.id = b.id", "b.k = a.k"],
+            10,
+            inner_join(
+                &["a.id = b.id"],
+                100,
+                scan("a", &["id", "k"], 100),
+                scan("b", &["id", "k"], 10),
+            ),
+            scan("c", &["id"], 50),
+        ));
+        let weights = weighted_join_graph(&plan);
+        let pair = |a: &str, b: &str| (a.to_string(), b.to_string());
+        assert_eq!(weights.len(), 2);
+        // 0.1 for the join of a and b, but the top join relates them too
+        assert!((weights[&pair("a", "b")] - 0.002).abs() < 1e-12);
+        assert!((weights[&pair("b", "c")] - 0.002).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,2125 @@
+use std::{error::Error, fs, io, path};
+use serde::{Deserialize, Serialize};
+use derivative::Derivative;
+
+pub mod analysis;
+pub mod compare;
+pub mod cost;
+pub mod export;
+pub mod graph;
+pub mod order;
+#[cfg(feature = "duckdb")]
+pub mod profile;
+pub mod rewrite;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod zipper;
+
+/// Preorder position of a node in its plan, assigned at load time.
+pub type NodeId = usize;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub enum JoinType {
+    Inner,
+    LeftOuter,
+    RightOuter,
+    FullOuter,
+    Mark,
+    Semi,
+    Anti,
+}
+
+/// An input of a binary join: the left side is the first child (probe side
+/// of a hash join), the right side the second.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Derivative)]
+#[derivative(PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Attribute {
+    pub table_name: String,
+    pub attr_name: String,
+    // SQL type, when the profile lists one for a scanned column
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[derivative(PartialEq = "ignore")]
+    #[derivative(Hash = "ignore")]
+    #[derivative(PartialOrd = "ignore")]
+    #[derivative(Ord = "ignore")]
+    pub data_type: Option<String>,
+}
+
+impl Attribute {
+    pub fn new(table_name: impl Into<String>, attr_name: impl Into<String>) -> Self {
+        Attribute {
+            table_name: table_name.into(),
+            attr_name: attr_name.into(),
+            data_type: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for Attribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.table_name, self.attr_name)?;
+        if let Some(data_type) = &self.data_type {
+            write!(f, " ({})", data_type)?;
+        }
+        Ok(())
+    }
+}
+
+/// `t.a`, or just `a` when the table is unknown. The alternate form `{:#}`
+/// appends the type when known, as in `t.a (INTEGER)`.
+impl std::fmt::Display for Attribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.table_name.is_empty() {
+            write!(f, "{}", self.attr_name)?;
+        } else {
+            write!(f, "{}.{}", self.table_name, self.attr_name)?;
+        }
+        match &self.data_type {
+            Some(data_type) if f.alternate() => write!(f, " ({})", data_type),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Condition {
+    pub left_attr: Attribute,
+    pub right_attr: Attribute,
+}
+
+impl Condition {
+    /// The same condition with its two sides ordered by (table, attribute),
+    /// so that `a.x = b.y` and `b.y = a.x` compare equal.
+    pub fn canonical(&self) -> Condition {
+        let (left_attr, right_attr) = self.sides();
+        Condition {
+            left_attr: left_attr.clone(),
+            right_attr: right_attr.clone(),
+        }
+    }
+
+    fn sides(&self) -> (&Attribute, &Attribute) {
+        if self.left_attr <= self.right_attr {
+            (&self.left_attr, &self.right_attr)
+        } else {
+            (&self.right_attr, &self.left_attr)
+        }
+    }
+}
+
+// Conditions sort by their canonical form; a condition and its mirror image
+// are adjacent, with the already-canonical one first.
+impl Ord for Condition {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.sides().cmp(&other.sides()).then_with(|| {
+            (&self.left_attr, &self.right_attr).cmp(&(&other.left_attr, &other.right_attr))
+        })
+    }
+}
+
+impl PartialOrd for Condition {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {}", self.left_attr, self.right_attr)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Join {
+    pub join_type: JoinType,
+    pub equalizers: Vec<Condition>,
+    // only set for mark joins
+    pub mark_column: Option<Attribute>,
+    // the input whose rows survive a semi, anti, left or right outer join;
+    // None for the other join types
+    pub preserved_side: Option<Side>,
+    // whether a HASH_GROUP_BY on the join keys sits directly above, see
+    // `rewrite::fuse_join_aggregate`
+    #[serde(default)]
+    pub followed_by_aggregate: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Scan {
+    pub table_name: String,
+    pub attributes: Vec<Attribute>,
+    // predicates pushed down into the scan
+    pub filters: Vec<Predicate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Filter {
+    // the conjuncts of the filter condition
+    pub predicates: Vec<Predicate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Ne,
+    Like,
+    In,
+    Between,
+}
+
+impl std::fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self {
+            CompareOp::Eq => "=",
+            CompareOp::Lt => "<",
+            CompareOp::Gt => ">",
+            CompareOp::Le => "<=",
+            CompareOp::Ge => ">=",
+            CompareOp::Ne => "<>",
+            CompareOp::Like => "LIKE",
+            CompareOp::In => "IN",
+            CompareOp::Between => "BETWEEN",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub enum Value {
+    Attribute(Attribute),
+    // kept as written, including any quotes
+    Literal(String),
+    // the values of an IN list, or the two bounds of a BETWEEN
+    List(Vec<Value>),
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Attribute(attr) => write!(f, "{}", attr),
+            Value::Literal(s) => write!(f, "{}", s),
+            Value::List(values) => {
+                let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "({})", values.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub enum Predicate {
+    Compare {
+        left: Attribute,
+        op: CompareOp,
+        right: Value,
+    },
+    // a predicate too complex to break down, kept verbatim
+    Raw(String),
+}
+
+impl std::fmt::Display for Predicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Predicate::Compare {
+                left,
+                op: CompareOp::Between,
+                right: Value::List(bounds),
+            } if bounds.len() == 2 => {
+                write!(f, "{} BETWEEN {} AND {}", left, bounds[0], bounds[1])
+            }
+            Predicate::Compare { left, op, right } => write!(f, "{} {} {}", left, op, right),
+            Predicate::Raw(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Project {
+    columns: Vec<Attribute>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct AggregateFunc {
+    pub name: String,
+    pub args: Vec<Attribute>,
+    pub distinct: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub enum AggregateExpr {
+    Func(AggregateFunc),
+    // an aggregate over an expression we don't parse, kept verbatim
+    Raw(String),
+}
+
+impl std::fmt::Display for AggregateExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AggregateExpr::Func(func) => {
+                let args: Vec<_> = func.args.iter().map(|a| a.to_string()).collect();
+                let distinct = if func.distinct { "DISTINCT " } else { "" };
+                write!(f, "{}({}{})", func.name, distinct, args.join(", "))
+            }
+            AggregateExpr::Raw(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Aggregate {
+    pub groups: Vec<Attribute>,
+    pub aggregates: Vec<AggregateExpr>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct RelationRef {
+    pub table_name: String,
+}
+
+/// A single inner join over any number of inputs, as evaluated by a
+/// worst-case optimal join. Not produced by the parser; see
+/// `rewrite::collapse_inner_joins`.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct MultiwayJoin {
+    pub relations: Vec<RelationRef>,
+    pub conditions: Vec<Condition>,
+}
+
+/// A read of a common table expression's result, which analyses treat as a
+/// base relation named after the CTE.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct CteScan {
+    pub name: String,
+}
+
+/// A recursive CTE; its first child is the anchor part and its second the
+/// recursive part.
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct RecursiveCte {
+    pub name: String,
+}
+
+// serialized with the variant in a "kind" field next to the operator's own
+// fields, e.g. {"kind": "Scan", "table_name": ...}
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+#[serde(tag = "kind")]
+pub enum Op {
+    Join(Join),
+    Scan(Scan),
+    Project(Project),
+    Aggregate(Aggregate),
+    Filter(Filter),
+    MultiwayJoin(MultiwayJoin),
+    CteScan(CteScan),
+    RecursiveCte(RecursiveCte),
+}
+
+impl Op {
+    /// The equi-join conditions of an inner or multiway join; empty for
+    /// every other operator.
+    pub fn inner_join_conditions(&self) -> &[Condition] {
+        match self {
+            Op::Join(join) if join.join_type == JoinType::Inner => &join.equalizers,
+            Op::MultiwayJoin(multiway) => &multiway.conditions,
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Derivative)]
+#[derivative(Debug, Hash, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Node {
+    #[serde(default)]
+    #[derivative(PartialEq = "ignore")]
+    #[derivative(Hash = "ignore")]
+    pub id: NodeId,
+    pub name: String,
+    // in seconds once loaded, whatever unit the profile used; None when the
+    // profile didn't measure it (plain EXPLAIN), see `timing_or_zero`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[derivative(PartialEq = "ignore")]
+    #[derivative(Hash = "ignore")]
+    pub timing: Option<f64>,
+    // total query time in seconds, which DuckDB reports on the root only
+    #[serde(rename = "result", default, skip_serializing_if = "Option::is_none")]
+    #[derivative(PartialEq = "ignore")]
+    #[derivative(Hash = "ignore")]
+    pub total_timing: Option<f64>,
+    pub cardinality: u64,
+    // estimated cost of the subtree, see `cost::annotate_costs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[derivative(PartialEq = "ignore")]
+    #[derivative(Hash = "ignore")]
+    pub cost: Option<f64>,
+    // whether `cardinality` is exact rather than sampled, when the profile says
+    #[serde(rename = "exact", default, skip_serializing_if = "Option::is_none")]
+    pub exact_cardinality: Option<bool>,
+    #[serde(deserialize_with = "deserialize_extra_info")]
+    pub extra_info: String,
+    pub children: Vec<Box<Node>>,
+    pub attr: Option<Op>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    UnknownNodeType(String),
+    UnknownJoinType(String),
+    // extra_info is missing a section or predicate side the parser expects
+    MalformedExtraInfo { node: String, text: String },
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnknownNodeType(name) => write!(f, "Unknown node type {}", name),
+            ParseError::UnknownJoinType(name) => write!(f, "Fail to parse Join Type {}", name),
+            ParseError::MalformedExtraInfo { node, text } => {
+                write!(f, "Malformed extra_info for {}: {:?}", node, text)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// What to do with a node type the parser has no mapping for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownPolicy {
+    // fail with ParseError::UnknownNodeType
+    #[default]
+    Strict,
+    // leave attr = None, so topology-only analyses still work
+    Skip,
+}
+
+/// How the parser handles a node whose `extra_info` it can't parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    // fail with the node's ParseError
+    #[default]
+    Strict,
+    // leave attr = None and record a Warning, whatever `on_unknown` says
+    Lenient,
+}
+
+/// A node the parser left unparsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub node: NodeId,
+    pub error: ParseError,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "node {}: {}", self.node, self.error)
+    }
+}
+
+/// A loaded profile with the nodes that were left unparsed, in inorder.
+#[derive(Debug)]
+pub struct ParseReport {
+    pub tree: Node,
+    pub warnings: Vec<Warning>,
+}
+
+/// What table name to give columns the profile doesn't qualify, such as
+/// projection and group-by columns.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum UnqualifiedPolicy {
+    // leave the table name empty
+    #[default]
+    Empty,
+    // use the given sentinel as the table name
+    Deadbeef(String),
+    // look the column up in the scans below, see `rewrite::resolve_unqualified`
+    ResolveFromScans,
+}
+
+/// Unit of the `timing` fields in a profile file, which depends on the
+/// DuckDB version that wrote it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    Seconds,
+    Milliseconds,
+}
+
+impl TimeUnit {
+    /// Guesses the unit from the root's `result`, which is always in
+    /// seconds: operator timings far larger than the total query time must
+    /// be milliseconds. Without a `result`, seconds are assumed.
+    pub fn detect(root: &Node) -> TimeUnit {
+        let Some(total) = root.total_timing.filter(|t| *t > 0.0) else {
+            return TimeUnit::Seconds;
+        };
+        let mut max_timing: f64 = 0.0;
+        preorder_traverse(root, &mut |node: &Node| {
+            max_timing = max_timing.max(node.timing_or_zero());
+        });
+        if max_timing > total * 10.0 {
+            TimeUnit::Milliseconds
+        } else {
+            TimeUnit::Seconds
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    // keep each node's raw extra_info after parsing it into attr; false
+    // clears it to save memory
+    pub keep_extra_info: bool,
+    pub on_unknown: UnknownPolicy,
+    pub strictness: Strictness,
+    // unit of the profile's timings; detected when None
+    pub time_unit: Option<TimeUnit>,
+    pub unqualified_table: UnqualifiedPolicy,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            keep_extra_info: true,
+            on_unknown: UnknownPolicy::Strict,
+            strictness: Strictness::Strict,
+            time_unit: None,
+            unqualified_table: UnqualifiedPolicy::Empty,
+        }
+    }
+}
+
+/// Loads the profile in `file_name`, or from stdin if it is `-`.
+pub fn get_join_tree(file_name: &str) -> Result<Node, Box<dyn Error>> {
+    if file_name == "-" {
+        return from_reader(io::stdin().lock());
+    }
+    let sql = fs::read_to_string(path::Path::new(file_name))?;
+    from_str(sql.as_str())
+}
+
+pub fn from_reader(mut reader: impl io::Read) -> Result<Node, Box<dyn Error>> {
+    let mut s = String::new();
+    reader.read_to_string(&mut s)?;
+    from_str(&s)
+}
+
+pub fn from_str(s: &str) -> Result<Node, Box<dyn Error>> {
+    from_str_with(s, &ParseOptions::default())
+}
+
+pub fn from_str_with(s: &str, opts: &ParseOptions) -> Result<Node, Box<dyn Error>> {
+    from_str_with_skipped(s, opts).map(|(root, _)| root)
+}
+
+/// Like `from_str_with`, but also returns the distinct node types that were
+/// left unparsed under `UnknownPolicy::Skip`, in the order first seen.
+pub fn from_str_with_skipped(
+    s: &str,
+    opts: &ParseOptions,
+) -> Result<(Node, Vec<String>), Box<dyn Error>> {
+    let report = from_str_report(s, opts)?;
+    Ok((report.tree, skipped_types(&report.warnings)))
+}
+
+/// Like `from_str_with`, but also returns the nodes left unparsed: those of
+/// unknown type under `UnknownPolicy::Skip`, and under `Strictness::Lenient`
+/// any node that failed to parse. Malformed JSON is an error either way.
+pub fn from_str_report(s: &str, opts: &ParseOptions) -> Result<ParseReport, Box<dyn Error>> {
+    let root: Node = serde_json::from_str(s)?;
+    prepare_tree(root, opts)
+}
+
+// the distinct node types of the warnings about unknown types, in order
+fn skipped_types(warnings: &[Warning]) -> Vec<String> {
+    let mut skipped = Vec::new();
+    for warning in warnings {
+        if let ParseError::UnknownNodeType(name) = &warning.error {
+            if !skipped.contains(name) {
+                skipped.push(name.clone());
+            }
+        }
+    }
+    skipped
+}
+
+/// Loads only the shape of the profile: timings are normalized and ids
+/// assigned as by `from_str`, but `extra_info` is left unparsed and every
+/// `attr` is `None`, so profiles with node types or `extra_info` layouts the
+/// parser doesn't know still load.
+pub fn from_str_structure_only(s: &str) -> Result<Node, Box<dyn Error>> {
+    let mut root: Node = serde_json::from_str(s)?;
+    normalize_timings(&mut root, None);
+    assign_ids(&mut root);
+    Ok(root)
+}
+
+/// Like `from_str`, but first unwraps the containers some tools put around
+/// the profile: an object holding the plan under a `result` or `plan` key,
+/// or a single-element array.
+pub fn from_json_auto(s: &str) -> Result<Node, Box<dyn Error>> {
+    plan_from_value(serde_json::from_str(s)?)
+}
+
+/// Loads every statement of a profile file: a top-level array holds one
+/// profile per statement, anything else is a single statement. Each profile
+/// may be wrapped as accepted by `from_json_auto`.
+pub fn load_all(path: &path::Path) -> Result<Vec<Node>, Box<dyn Error>> {
+    let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    match value {
+        serde_json::Value::Array(items) => items.into_iter().map(plan_from_value).collect(),
+        value => Ok(vec![plan_from_value(value)?]),
+    }
+}
+
+/// Parses every file directly in `dir` whose name matches the glob
+/// `pattern` (e.g. `tpch_q*.json`), sorted by path. A file that fails to
+/// load gets its error in place of a plan; only failing to read `dir`
+/// itself is an error.
+#[cfg(feature = "glob")]
+pub fn load_dir(dir: &path::Path, pattern: &str) -> Result<Vec<LoadedFile>, Box<dyn Error>> {
+    load_dir_with(dir, pattern, false)
+}
+
+/// Like `load_dir`, but also descends into subdirectories.
+#[cfg(feature = "glob")]
+pub fn load_dir_recursive(
+    dir: &path::Path,
+    pattern: &str,
+) -> Result<Vec<LoadedFile>, Box<dyn Error>> {
+    load_dir_with(dir, pattern, true)
+}
+
+#[cfg(feature = "glob")]
+pub type LoadedFile = (path::PathBuf, Result<Node, Box<dyn Error>>);
+
+#[cfg(feature = "glob")]
+fn load_dir_with(
+    dir: &path::Path,
+    pattern: &str,
+    recursive: bool,
+) -> Result<Vec<LoadedFile>, Box<dyn Error>> {
+    fn matching_files(
+        dir: &path::Path,
+        pattern: &glob::Pattern,
+        recursive: bool,
+        files: &mut Vec<path::PathBuf>,
+    ) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if recursive {
+                    matching_files(&path, pattern, recursive, files)?;
+                }
+            } else if path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| pattern.matches(n))
+            {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+    let pattern = glob::Pattern::new(pattern)?;
+    let mut files = Vec::new();
+    matching_files(dir, &pattern, recursive, &mut files)?;
+    files.sort();
+    Ok(files
+        .into_iter()
+        .map(|path| {
+            let plan = fs::read_to_string(&path)
+                .map_err(Into::into)
+                .and_then(|s| from_str(&s));
+            (path, plan)
+        })
+        .collect())
+}
+
+fn plan_from_value(mut value: serde_json::Value) -> Result<Node, Box<dyn Error>> {
+    loop {
+        let looks_like_plan = value.get("name").is_some() && value.get("children").is_some();
+        if looks_like_plan {
+            break;
+        }
+        value = match value {
+            serde_json::Value::Object(mut map) => {
+                let inner = ["result", "plan"]
+                    .iter()
+                    .find(|k| map.get(**k).is_some_and(|v| v.is_object() || v.is_array()));
+                match inner {
+                    Some(key) => map.remove(*key).unwrap(),
+                    None => {
+                        let keys: Vec<_> = map.keys().map(|k| k.as_str()).collect();
+                        return Err(format!(
+                            "Fail to locate a plan, found keys [{}]",
+                            keys.join(", ")
+                        )
+                        .into());
+                    }
+                }
+            }
+            serde_json::Value::Array(mut items) if items.len() == 1 => items.remove(0),
+            serde_json::Value::Array(items) => {
+                return Err(format!(
+                    "Fail to locate a plan, found an array of {} elements",
+                    items.len()
+                )
+                .into())
+            }
+            _ => return Err("Fail to locate a plan, found a non-object value".into()),
+        };
+    }
+    let root: Node = serde_json::from_value(value)?;
+    prepare_tree(root, &ParseOptions::default()).map(|report| report.tree)
+}
+
+fn prepare_tree(mut root: Node, opts: &ParseOptions) -> Result<ParseReport, Box<dyn Error>> {
+    // numbered first, so that warnings can point at nodes
+    assign_ids(&mut root);
+    let warnings = parse_tree_collecting(&mut root, opts.on_unknown, opts.strictness)?;
+    normalize_timings(&mut root, opts.time_unit);
+    match &opts.unqualified_table {
+        UnqualifiedPolicy::Empty => {}
+        UnqualifiedPolicy::Deadbeef(table) => rewrite::map_attributes(&mut root, |attr| {
+            if attr.table_name.is_empty() {
+                attr.table_name = table.clone();
+            }
+        }),
+        UnqualifiedPolicy::ResolveFromScans => rewrite::resolve_unqualified(&mut root),
+    }
+    if !opts.keep_extra_info {
+        inorder_traverse_mut(&mut root, &mut |node: &mut Node| node.extra_info.clear());
+    }
+    Ok(ParseReport {
+        tree: root,
+        warnings,
+    })
+}
+
+// converts the timings of the tree to seconds, detecting the unit if None
+fn normalize_timings(root: &mut Node, unit: Option<TimeUnit>) {
+    let unit = unit.unwrap_or_else(|| TimeUnit::detect(root));
+    if unit == TimeUnit::Milliseconds {
+        inorder_traverse_mut(root, &mut |node: &mut Node| {
+            if let Some(timing) = &mut node.timing {
+                *timing /= 1000.0;
+            }
+        });
+    }
+}
+
+/// Numbers the nodes of the tree in preorder, starting from 0 at the root.
+pub fn assign_ids(root: &mut Node) {
+    fn assign(node: &mut Node, next: &mut NodeId) {
+        node.id = *next;
+        *next += 1;
+        for child in &mut node.children {
+            assign(child, next);
+        }
+    }
+    assign(root, &mut 0);
+}
+
+impl Node {
+    /// The operator's timing in seconds, or 0 when it wasn't measured.
+    pub fn timing_or_zero(&self) -> f64 {
+        self.timing.unwrap_or(0.0)
+    }
+
+    pub fn timing_s(&self) -> f64 {
+        self.timing_or_zero()
+    }
+
+    pub fn timing_ms(&self) -> f64 {
+        self.timing_or_zero() * 1000.0
+    }
+
+    pub fn find(&self, id: NodeId) -> Option<&Node> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|c| c.find(id))
+    }
+
+    /// A copy of this node with `children` in place of its own, which aren't
+    /// cloned.
+    pub fn with_children(&self, children: Vec<Box<Node>>) -> Node {
+        Node {
+            id: self.id,
+            name: self.name.clone(),
+            timing: self.timing,
+            total_timing: self.total_timing,
+            cardinality: self.cardinality,
+            cost: self.cost,
+            exact_cardinality: self.exact_cardinality,
+            extra_info: self.extra_info.clone(),
+            children,
+            attr: self.attr.clone(),
+        }
+    }
+
+    pub fn as_join(&self) -> Option<&Join> {
+        match &self.attr {
+            Some(Op::Join(join)) => Some(join),
+            _ => None,
+        }
+    }
+
+    pub fn as_scan(&self) -> Option<&Scan> {
+        match &self.attr {
+            Some(Op::Scan(scan)) => Some(scan),
+            _ => None,
+        }
+    }
+
+    pub fn as_projection(&self) -> Option<&Project> {
+        match &self.attr {
+            Some(Op::Project(project)) => Some(project),
+            _ => None,
+        }
+    }
+
+    pub fn as_filter(&self) -> Option<&Filter> {
+        match &self.attr {
+            Some(Op::Filter(filter)) => Some(filter),
+            _ => None,
+        }
+    }
+
+    pub fn as_aggregate(&self) -> Option<&Aggregate> {
+        match &self.attr {
+            Some(Op::Aggregate(aggregate)) => Some(aggregate),
+            _ => None,
+        }
+    }
+
+    pub fn is_join(&self) -> bool {
+        self.as_join().is_some()
+    }
+
+    pub fn is_scan(&self) -> bool {
+        self.as_scan().is_some()
+    }
+
+    pub fn is_projection(&self) -> bool {
+        self.as_projection().is_some()
+    }
+
+    pub fn is_filter(&self) -> bool {
+        self.as_filter().is_some()
+    }
+
+    pub fn is_aggregate(&self) -> bool {
+        self.as_aggregate().is_some()
+    }
+
+    /// A one-line description of the operator and its key details, e.g.
+    /// `HASH_JOIN Inner: a.id = b.id` or `SEQ_SCAN title (3 columns)`.
+    pub fn summary(&self) -> String {
+        match &self.attr {
+            Some(Op::Join(join)) => {
+                let conditions: Vec<_> = join.equalizers.iter().map(|c| c.to_string()).collect();
+                let mut summary = format!(
+                    "{} {:?}: {}",
+                    self.name,
+                    join.join_type,
+                    conditions.join(" AND ")
+                );
+                if let Some(mark) = &join.mark_column {
+                    summary.push_str(&format!(" -> {}", mark));
+                }
+                summary
+            }
+            Some(Op::Scan(scan)) => {
+                let mut summary = format!(
+                    "{} {} ({} columns",
+                    self.name,
+                    scan.table_name,
+                    scan.attributes.len()
+                );
+                if !scan.filters.is_empty() {
+                    summary.push_str(&format!(", {} filters", scan.filters.len()));
+                }
+                summary + ")"
+            }
+            Some(Op::Project(project)) => {
+                format!("{} ({} columns)", self.name, project.columns.len())
+            }
+            Some(Op::Aggregate(aggregate)) => format!(
+                "{} ({} groups, {} aggregates)",
+                self.name,
+                aggregate.groups.len(),
+                aggregate.aggregates.len()
+            ),
+            Some(Op::Filter(filter)) => {
+                let predicates: Vec<_> = filter.predicates.iter().map(|p| p.to_string()).collect();
+                format!("{}: {}", self.name, predicates.join(" AND "))
+            }
+            Some(Op::CteScan(cte)) => format!("{} {}", self.name, cte.name),
+            Some(Op::RecursiveCte(cte)) => format!("{} {}", self.name, cte.name),
+            Some(Op::MultiwayJoin(multiway)) => {
+                let conditions: Vec<_> =
+                    multiway.conditions.iter().map(|c| c.to_string()).collect();
+                format!(
+                    "{} of {} relations: {}",
+                    self.name,
+                    multiway.relations.len(),
+                    conditions.join(" AND ")
+                )
+            }
+            None => self.name.clone(),
+        }
+    }
+}
+
+// strips parentheses around the whole of `s`, e.g. `((a AND b))` => `a AND b`
+fn strip_parens(mut s: &str) -> &str {
+    loop {
+        s = s.trim();
+        let Some(inner) = s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) else {
+            return s;
+        };
+        // `(a) AND (b)` starts and ends with a parenthesis but isn't wrapped
+        let mut depth = 0;
+        for c in inner.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => return s,
+                ')' => depth -= 1,
+                _ => {}
+            }
+        }
+        s = inner;
+    }
+}
+
+// splits a predicate on its top-level ANDs, i.e. those outside parentheses
+// and string literals
+fn split_conjuncts(s: &str) -> Vec<String> {
+    let s = strip_parens(s);
+    let mut conjuncts = Vec::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    // the AND of `x BETWEEN a AND b` doesn't separate conjuncts
+    let mut in_between = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '\'' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => depth -= 1,
+            ' ' if !in_string && depth == 0 && s[i..].starts_with(" BETWEEN ") => {
+                in_between = true;
+            }
+            ' ' if !in_string && depth == 0 && s[i..].starts_with(" AND ") => {
+                if in_between {
+                    in_between = false;
+                } else {
+                    conjuncts.push(&s[start..i]);
+                    start = i + " AND ".len();
+                }
+            }
+            _ => {}
+        }
+    }
+    conjuncts.push(&s[start..]);
+    conjuncts
+        .into_iter()
+        .map(strip_parens)
+        .filter(|c| !c.is_empty())
+        .map(|c| c.to_string())
+        .collect()
+}
+
+// splits on `sep` where it isn't inside a double-quoted identifier
+fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == sep && !in_quotes {
+            parts.push(&s[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+// `"my.table".col` => ["my.table", "col"]
+fn split_identifier(s: &str) -> Vec<String> {
+    split_unquoted(s, '.')
+        .into_iter()
+        .map(|part| {
+            let part = part.trim();
+            match part.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+                Some(quoted) => quoted.replace("\"\"", "\""),
+                None => part.to_string(),
+            }
+        })
+        .collect()
+}
+
+// a scanned column, optionally followed by its type as in `l_orderkey (INTEGER)`
+fn scan_attribute(table_name: &str, s: &str) -> Attribute {
+    let mut attribute = Attribute::new(table_name, s);
+    if let Some((name, data_type)) = s.strip_suffix(')').and_then(|s| s.split_once(" (")) {
+        attribute.attr_name = name.trim().to_string();
+        attribute.data_type = Some(data_type.trim().to_string());
+    }
+    attribute
+}
+
+fn projection_attribute(s: &str) -> Attribute {
+    let names = split_identifier(s);
+    // HACK similar to join_attribute, we use "" as deadbeef
+    // and get the table name from profile by the patched duckdb.
+    if names.len() == 1 {
+        Attribute::new("", names[0].as_str())
+    } else {
+        Attribute::new(names[0].as_str(), names[1].as_str())
+    }
+}
+
+fn parse_aggregate_expr(s: &str) -> AggregateExpr {
+    match parse_aggregate_func(s) {
+        Some(func) => AggregateExpr::Func(func),
+        None => AggregateExpr::Raw(s.to_string()),
+    }
+}
+
+// DuckDB writes aggregates as `count_star()`, `sum(#2)` or
+// `count(DISTINCT o_custkey)`; anything with a non-column argument is
+// left to the caller as a raw string
+fn parse_aggregate_func(s: &str) -> Option<AggregateFunc> {
+    let (name, rest) = s.split_once('(')?;
+    let name = name.trim();
+    let args = rest.strip_suffix(')')?.trim();
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    let (distinct, args) = match args.strip_prefix("DISTINCT ") {
+        Some(args) => (true, args),
+        None => (false, args),
+    };
+    let args = if args.is_empty() {
+        Vec::new()
+    } else {
+        split_unquoted(args, ',')
+            .into_iter()
+            .map(|a| a.trim())
+            .map(|a| is_column(a).then(|| projection_attribute(a)))
+            .collect::<Option<Vec<_>>>()?
+    };
+    Some(AggregateFunc {
+        name: name.to_string(),
+        args,
+        distinct,
+    })
+}
+
+// a plain or double-quoted column reference, possibly qualified
+fn is_column(s: &str) -> bool {
+    split_unquoted(s, '.').iter().all(|part| {
+        let part = part.trim();
+        let quoted = part.len() >= 2 && part.starts_with('"') && part.ends_with('"');
+        let plain = !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '#');
+        quoted || plain
+    })
+}
+
+// position and operator of the first comparison outside parentheses and
+// string literals; `!~~` (NOT LIKE) is found but has no CompareOp
+fn find_compare_op(s: &str) -> Option<(usize, &'static str, Option<CompareOp>)> {
+    const OPS: [(&str, Option<CompareOp>); 13] = [
+        (" BETWEEN ", Some(CompareOp::Between)),
+        (" IN ", Some(CompareOp::In)),
+        (" LIKE ", Some(CompareOp::Like)),
+        ("!~~", None),
+        ("~~", Some(CompareOp::Like)),
+        ("<=", Some(CompareOp::Le)),
+        (">=", Some(CompareOp::Ge)),
+        ("<>", Some(CompareOp::Ne)),
+        ("!=", Some(CompareOp::Ne)),
+        ("==", Some(CompareOp::Eq)),
+        ("=", Some(CompareOp::Eq)),
+        ("<", Some(CompareOp::Lt)),
+        (">", Some(CompareOp::Gt)),
+    ];
+    let mut depth = 0;
+    let mut in_string = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '\'' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => depth -= 1,
+            _ if !in_string && depth == 0 => {
+                if let Some((token, op)) = OPS.iter().find(|(token, _)| s[i..].starts_with(token)) {
+                    return Some((i, token, *op));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// a single literal: a quoted string, or a token such as `2005`, `actor`
+// or `[ru]` as written in scan filters
+fn parse_literal(s: &str) -> Option<Value> {
+    let s = s.trim();
+    let quoted = s.len() >= 2
+        && s.starts_with('\'')
+        && s.ends_with('\'')
+        && !s[1..s.len() - 1].replace("''", "").contains('\'');
+    let token = !s.is_empty() && !s.contains(|c: char| c.is_whitespace() || "()'".contains(c));
+    (quoted || token).then(|| Value::Literal(s.to_string()))
+}
+
+fn parse_value(s: &str) -> Option<Value> {
+    let s = s.trim();
+    // `t.id` is a column, `1.5` is not
+    let qualified = split_unquoted(s, '.').len() == 2
+        && s.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '"');
+    if qualified && is_column(s) {
+        Some(Value::Attribute(projection_attribute(s)))
+    } else {
+        parse_literal(s)
+    }
+}
+
+// comma-separated values, commas in string literals excepted
+fn parse_value_list(s: &str) -> Option<Vec<Value>> {
+    let mut values = Vec::new();
+    let mut in_string = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '\'' => in_string = !in_string,
+            ',' if !in_string => {
+                values.push(parse_value(&s[start..i])?);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    values.push(parse_value(&s[start..])?);
+    Some(values)
+}
+
+fn parse_predicate(s: &str) -> Predicate {
+    parse_comparison(s).unwrap_or_else(|| Predicate::Raw(s.to_string()))
+}
+
+// `column op value`; anything else (function calls, IS NULL, OR, arithmetic)
+// is left to the caller as a raw string
+fn parse_comparison(s: &str) -> Option<Predicate> {
+    let s = strip_parens(s);
+    let (i, token, op) = find_compare_op(s)?;
+    let op = op?;
+    let left = s[..i].trim();
+    let right = s[i + token.len()..].trim();
+    if !is_column(left) {
+        return None;
+    }
+    let right = match op {
+        CompareOp::In => Value::List(parse_value_list(
+            right.strip_prefix('(')?.strip_suffix(')')?,
+        )?),
+        CompareOp::Between => {
+            let (low, high) = right.split_once(" AND ")?;
+            Value::List(vec![parse_value(low)?, parse_value(high)?])
+        }
+        _ => parse_value(right)?,
+    };
+    Some(Predicate::Compare {
+        left: projection_attribute(left),
+        op,
+        right,
+    })
+}
+
+fn join_attribute(s: &str) -> Attribute {
+    let names = split_identifier(s);
+    // HACK in the profile generated by unmodified duckdb
+    // the table name is not included in the attribute name.
+    // Here we use the attribute name as deadbeef,
+    // and get the table name from the profile generated
+    // by patched duckdb
+    if names.len() == 1 {
+        Attribute::new(names[0].as_str(), names[0].as_str())
+    } else {
+        Attribute::new(names[0].as_str(), names[1].as_str())
+    }
+}
+
+/// Parses every node's `extra_info` into its `attr`. Any existing `attr` is
+/// discarded first, so this can be re-run on a tree (e.g. after a parser fix)
+/// as long as `extra_info` was kept: a tree loaded with `keep_extra_info:
+/// false` has nothing left to parse and can't be re-parsed.
+pub fn parse_tree_extra_info(root: &mut Node) -> Result<(), ParseError> {
+    parse_tree_extra_info_with(root, UnknownPolicy::Strict).map(|_| ())
+}
+
+/// Like `parse_tree_extra_info`, returning the distinct node types skipped
+/// under `UnknownPolicy::Skip`.
+pub fn parse_tree_extra_info_with(
+    root: &mut Node,
+    on_unknown: UnknownPolicy,
+) -> Result<Vec<String>, ParseError> {
+    parse_tree_collecting(root, on_unknown, Strictness::Strict).map(|w| skipped_types(&w))
+}
+
+fn parse_tree_collecting(
+    root: &mut Node,
+    on_unknown: UnknownPolicy,
+    strictness: Strictness,
+) -> Result<Vec<Warning>, ParseError> {
+    let mut warnings = Vec::new();
+    let mut parse_func = |node: &mut Node| {
+        node.attr = None;
+        match parse_node_extra_info(node) {
+            Err(error) => {
+                let skip = strictness == Strictness::Lenient
+                    || (on_unknown == UnknownPolicy::Skip
+                        && matches!(error, ParseError::UnknownNodeType(_)));
+                if !skip {
+                    return Err(error);
+                }
+                warnings.push(Warning {
+                    node: node.id,
+                    error,
+                });
+                Ok(())
+            }
+            Ok(()) => Ok(()),
+        }
+    };
+    try_inorder_traverse_mut(root, &mut parse_func)?;
+    Ok(warnings)
+}
+
+/// Re-parses the `extra_info` of an already loaded tree in place. Like
+/// `parse_tree_extra_info`, not possible on a tree loaded with
+/// `keep_extra_info: false`.
+pub fn reparse(root: &mut Node) -> Result<(), ParseError> {
+    parse_tree_extra_info(root)
+}
+
+// Newer DuckDB versions write `extra_info` as an object of named sections
+// (`{"Join Type": "INNER", "Conditions": "..."}`); those are laid out in the
+// older text form, which is what the parser reads.
+fn deserialize_extra_info<'de, D: serde::Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ExtraInfo {
+        Text(String),
+        Sections(serde_json::Map<String, serde_json::Value>),
+    }
+    Ok(match ExtraInfo::deserialize(d)? {
+        ExtraInfo::Text(text) => text,
+        ExtraInfo::Sections(sections) => extra_info_text(&sections),
+    })
+}
+
+fn extra_info_text(sections: &serde_json::Map<String, serde_json::Value>) -> String {
+    // a section is a string, or a list of lines
+    let section = |key: &str| match sections.get(key)? {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Array(lines) => Some(
+            lines
+                .iter()
+                .map(|l| l.as_str().map_or_else(|| l.to_string(), str::to_string))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        value => Some(value.to_string()),
+    };
+    if let Some(table) = section("Table") {
+        let mut text = format!(
+            "{}\n[INFOSEPARATOR]\n{}",
+            table,
+            section("Projections").unwrap_or_default()
+        );
+        if let Some(filters) = section("Filters") {
+            text.push_str("\n[INFOSEPARATOR]\n");
+            text.push_str(&filters);
+        }
+        return text;
+    }
+    if let Some(join_type) = section("Join Type") {
+        let conditions = section("Conditions").unwrap_or_default();
+        return format!("{}\n{}\n", join_type, conditions);
+    }
+    // the sections of other operators, in the order the text form has them
+    let ordered = [
+        "CTE Name",
+        "Groups",
+        "Aggregates",
+        "Projections",
+        "Expression",
+    ];
+    let known: Vec<_> = ordered.into_iter().filter_map(section).collect();
+    if !known.is_empty() {
+        return known.join("\n");
+    }
+    sections
+        .keys()
+        .filter(|k| *k != "Estimated Cardinality")
+        .filter_map(|k| section(k))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn malformed(node: &Node) -> ParseError {
+    ParseError::MalformedExtraInfo {
+        node: node.name.clone(),
+        text: node.extra_info.clone(),
+    }
+}
+
+fn parse_node_extra_info(node: &mut Node) -> Result<(), ParseError> {
+    match node.name.as_str() {
+        "HASH_JOIN" => {
+            let extra_info: Vec<_> = node
+                .extra_info
+                .split('\n')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .collect();
+
+            let Some(&join_type) = extra_info.first() else {
+                return Err(malformed(node));
+            };
+            let (join_type, preserved_side) = match join_type {
+                "INNER" => (JoinType::Inner, None),
+                "MARK" => (JoinType::Mark, None),
+                "LEFT" => (JoinType::LeftOuter, Some(Side::Left)),
+                "RIGHT" => (JoinType::RightOuter, Some(Side::Right)),
+                "OUTER" | "FULL" => (JoinType::FullOuter, None),
+                "SEMI" => (JoinType::Semi, Some(Side::Left)),
+                "ANTI" => (JoinType::Anti, Some(Side::Left)),
+                "RIGHT_SEMI" => (JoinType::Semi, Some(Side::Right)),
+                "RIGHT_ANTI" => (JoinType::Anti, Some(Side::Right)),
+                _ => return Err(ParseError::UnknownJoinType(join_type.to_string())),
+            };
+
+            let mut equalizers = Vec::new();
+            let mut mark_column = None;
+
+            for pred in &extra_info[1..] {
+                match find_compare_op(pred) {
+                    // non-equi conditions (`a.x < b.y`) aren't equalizers
+                    Some((_, _, op)) if op != Some(CompareOp::Eq) => continue,
+                    // the only line of a mark join that isn't a comparison
+                    // names the boolean marker column it emits for its parent
+                    None if join_type == JoinType::Mark => {
+                        mark_column = Some(join_attribute(pred));
+                        continue;
+                    }
+                    _ => {}
+                }
+                let equalizer = split_unquoted(pred, '=');
+                if equalizer.len() != 2 {
+                    return Err(malformed(node));
+                }
+                equalizers.push(Condition {
+                    left_attr: join_attribute(equalizer[0]),
+                    right_attr: join_attribute(equalizer[1]),
+                });
+            }
+
+            node.attr = Some(Op::Join(Join {
+                join_type,
+                equalizers,
+                mark_column,
+                preserved_side,
+                followed_by_aggregate: false,
+            }));
+        }
+        // index scans list their table, columns and filters like seq scans
+        "SEQ_SCAN" | "INDEX_SCAN" => {
+            let extra_info: Vec<_> = node.extra_info.split("[INFOSEPARATOR]").collect();
+            if extra_info.len() < 2 {
+                return Err(malformed(node));
+            }
+            let table_name = extra_info[0].trim();
+            let info_strs: Vec<_> = extra_info[1]
+                .split('\n')
+                .filter(|s| !s.is_empty())
+                .collect();
+            let mut filters: Vec<_> = match extra_info.get(2) {
+                Some(section) => section
+                    .split('\n')
+                    .map(|s| s.trim())
+                    .map(|s| s.strip_prefix("Filters:").unwrap_or(s))
+                    .flat_map(split_conjuncts)
+                    .map(|p| parse_predicate(&p))
+                    .collect(),
+                None => Vec::new(),
+            };
+            // scan filters name columns of the scanned table unqualified
+            for filter in &mut filters {
+                if let Predicate::Compare { left, .. } = filter {
+                    if left.table_name.is_empty() {
+                        left.table_name = table_name.to_string();
+                    }
+                }
+            }
+
+            node.attr = Some(Op::Scan(Scan {
+                table_name: table_name.to_string(),
+                attributes: info_strs
+                    .iter()
+                    .map(|s| scan_attribute(table_name, s))
+                    .collect(),
+                filters,
+            }));
+        }
+        "PROJECTION" => {
+            let columns: Vec<_> = node
+                .extra_info
+                .split('\n')
+                .filter(|s| !s.is_empty())
+                .map(projection_attribute)
+                .collect();
+            node.attr = Some(Op::Project(Project { columns }));
+        }
+        "SIMPLE_AGGREGATE" | "HASH_GROUP_BY" | "PERFECT_HASH_GROUP_BY" => {
+            let mut groups = Vec::new();
+            let mut aggregates = Vec::new();
+            for line in node.extra_info.split('\n').map(|s| s.trim()) {
+                if line.is_empty() {
+                    continue;
+                }
+                // group columns are plain column references, everything
+                // else is an aggregate expression
+                if line.contains('(') {
+                    aggregates.push(parse_aggregate_expr(line));
+                } else {
+                    groups.push(projection_attribute(line));
+                }
+            }
+            node.attr = Some(Op::Aggregate(Aggregate { groups, aggregates }));
+        }
+        "CTE_SCAN" | "RECURSIVE_CTE_SCAN" => {
+            let name = node.extra_info.lines().next().unwrap_or_default().trim();
+            node.attr = Some(Op::CteScan(CteScan {
+                name: name.to_string(),
+            }));
+        }
+        "RECURSIVE_CTE" => {
+            let name = node.extra_info.lines().next().unwrap_or_default().trim();
+            node.attr = Some(Op::RecursiveCte(RecursiveCte {
+                name: name.to_string(),
+            }));
+        }
+        // CTE materializes its first child for the CTE scans in its second
+        "CHUNK_SCAN" | "RESULT_COLLECTOR" | "Query" | "EXPLAIN_ANALYZE" | "ORDER_BY" | "TOP_N"
+        | "CTE" => {}
+        "FILTER" => {
+            let predicates = split_conjuncts(&node.extra_info.replace('\n', " "))
+                .iter()
+                .map(|p| parse_predicate(p))
+                .collect();
+            node.attr = Some(Op::Filter(Filter { predicates }));
+        }
+        _ => return Err(ParseError::UnknownNodeType(node.name.clone())),
+    };
+    Ok(())
+}
+
+fn try_inorder_traverse_mut<T, E>(node: &mut Node, func: &mut T) -> Result<(), E>
+where
+    T: FnMut(&mut Node) -> Result<(), E>,
+{
+    if !node.children.is_empty() {
+        try_inorder_traverse_mut(&mut node.children[0], func)?;
+    }
+    func(node)?;
+    if !node.children.is_empty() {
+        for child_node in &mut node.children[1..] {
+            try_inorder_traverse_mut(child_node, func)?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn inorder_traverse_mut<T>(node: &mut Node, func: &mut T)
+where
+    T: FnMut(&mut Node),
+{
+    if !node.children.is_empty() {
+        inorder_traverse_mut(&mut node.children[0], func);
+    }
+    func(node);
+    if !node.children.is_empty() {
+        for child_node in &mut node.children[1..] {
+            inorder_traverse_mut(child_node, func);
+        }
+    }
+}
+
+pub(crate) fn preorder_traverse<'a, T>(node: &'a Node, func: &mut T)
+where
+    T: FnMut(&'a Node),
+{
+    func(node);
+    for child_node in &node.children {
+        preorder_traverse(child_node, func);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+
+    fn json(root: &Node) -> String {
+        serde_json::to_string(root).unwrap()
+    }
+
+    #[test]
+    fn extra_info_cleared_only_when_asked() {
+        let profile = json(&scan("a", &["id"], 1));
+        let kept = from_str(&profile).unwrap();
+        assert!(!kept.extra_info.is_empty());
+        let opts = ParseOptions {
+            keep_extra_info: false,
+            ..Default::default()
+        };
+        let cleared = from_str_with(&profile, &opts).unwrap();
+        assert!(cleared.extra_info.is_empty());
+        assert_eq!(cleared.attr, kept.attr);
+    }
+
+    #[test]
+    fn mark_join_records_its_marker() {
+        let root = from_str(include_str!("../tests/data/in_subquery.json")).unwrap();
+        let join = root.find(4).unwrap();
+        let Some(Op::Join(join)) = &join.attr else {
+            panic!("expected a join, got {:?}", join.attr);
+        };
+        assert_eq!(join.join_type, JoinType::Mark);
+        assert_eq!(join.equalizers.len(), 1);
+        assert_eq!(
+            join.mark_column.as_ref().map(|m| m.attr_name.as_str()),
+            Some("SUBQUERY")
+        );
+
+        let inner = from_str(&json(&inner_join(
+            &["a.id = b.id"],
+            1,
+            scan("a", &["id"], 1),
+            scan("b", &["id"], 1),
+        )))
+        .unwrap();
+        assert!(matches!(
+            inner.attr,
+            Some(Op::Join(Join {
+                mark_column: None,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn from_json_auto_unwraps_containers() {
+        let plan = json(&scan("a", &["id"], 3));
+        let expected = from_str(&plan).unwrap();
+        for wrapped in [
+            plan.clone(),
+            format!(r#"{{"result": {}}}"#, plan),
+            format!(r#"{{"plan": {}, "query": "SELECT 1"}}"#, plan),
+            format!("[{}]", plan),
+            format!(r#"{{"result": [{}]}}"#, plan),
+        ] {
+            assert_eq!(from_json_auto(&wrapped).unwrap(), expected, "{}", wrapped);
+        }
+    }
+
+    #[test]
+    fn from_json_auto_lists_keys_when_no_plan() {
+        let err = from_json_auto(r#"{"query": "SELECT 1", "timing": 3}"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Fail to locate a plan, found keys [query, timing]"
+        );
+        let err = from_json_auto("[1, 2]").unwrap_err();
+        assert!(err.to_string().contains("array of 2 elements"));
+    }
+
+    #[test]
+    fn parses_aggregate_functions() {
+        let func = |name: &str, args: Vec<Attribute>, distinct| {
+            AggregateExpr::Func(AggregateFunc {
+                name: name.to_string(),
+                args,
+                distinct,
+            })
+        };
+        assert_eq!(
+            parse_aggregate_expr("count_star()"),
+            func("count_star", vec![], false)
+        );
+        assert_eq!(
+            parse_aggregate_expr("sum(l_extendedprice)"),
+            func("sum", vec![Attribute::new("", "l_extendedprice")], false)
+        );
+        assert_eq!(
+            parse_aggregate_expr("count(DISTINCT o.o_custkey)"),
+            func("count", vec![Attribute::new("o", "o_custkey")], true)
+        );
+        assert_eq!(
+            parse_aggregate_expr("sum((price * (1 - discount)))"),
+            AggregateExpr::Raw("sum((price * (1 - discount)))".to_string())
+        );
+    }
+
+    #[test]
+    fn attributes_sort_by_table_then_column() {
+        let mut attrs = vec![
+            Attribute::new("b", "a"),
+            Attribute::new("a", "z"),
+            Attribute::new("a", "b"),
+        ];
+        attrs.sort();
+        assert_eq!(
+            attrs,
+            vec![
+                Attribute::new("a", "b"),
+                Attribute::new("a", "z"),
+                Attribute::new("b", "a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_identifiers_keep_dots_and_spaces() {
+        assert_eq!(
+            split_identifier(r#""my.table".col"#),
+            vec!["my.table", "col"]
+        );
+        assert_eq!(split_identifier(r#"t."my col""#), vec!["t", "my col"]);
+        assert_eq!(
+            split_identifier(r#""say ""hi""".x"#),
+            vec![r#"say "hi""#, "x"]
+        );
+
+        let plan = json(&inner_join(
+            &[r#""my.table".id = b."my col""#],
+            1,
+            scan("my.table", &["id"], 1),
+            scan("b", &["my col"], 1),
+        ));
+        let root = from_str(&plan).unwrap();
+        assert_eq!(
+            root.attr.unwrap().inner_join_conditions(),
+            &[Condition {
+                left_attr: Attribute::new("my.table", "id"),
+                right_attr: Attribute::new("b", "my col"),
+            }]
+        );
+    }
+
+    #[test]
+    fn reports_missing_extra_info_sections() {
+        let mut bare_scan = node("SEQ_SCAN", "a", 1, vec![]);
+        assert_eq!(
+            parse_tree_extra_info(&mut bare_scan),
+            Err(ParseError::MalformedExtraInfo {
+                node: "SEQ_SCAN".to_string(),
+                text: "a".to_string(),
+            })
+        );
+        let mut empty_join = node(
+            "HASH_JOIN",
+            "",
+            1,
+            vec![scan("a", &["id"], 1), scan("b", &["id"], 1)],
+        );
+        assert_eq!(
+            parse_tree_extra_info(&mut empty_join),
+            Err(ParseError::MalformedExtraInfo {
+                node: "HASH_JOIN".to_string(),
+                text: String::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn summarizes_every_operator() {
+        let summary = |n: Node| parsed(n).summary();
+        assert_eq!(
+            summary(inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id"], 1),
+                scan("b", &["id"], 1)
+            )),
+            "HASH_JOIN Inner: a.id = b.id"
+        );
+        assert_eq!(
+            summary(node(
+                "SEQ_SCAN",
+                "a\n[INFOSEPARATOR]\nid\nx\n[INFOSEPARATOR]\nFilters: x>1",
+                1,
+                vec![]
+            )),
+            "SEQ_SCAN a (2 columns, 1 filters)"
+        );
+        assert_eq!(
+            summary(node("PROJECTION", "a\nb", 1, vec![])),
+            "PROJECTION (2 columns)"
+        );
+        assert_eq!(
+            summary(node("HASH_GROUP_BY", "#0\ncount_star()", 1, vec![])),
+            "HASH_GROUP_BY (1 groups, 1 aggregates)"
+        );
+        assert_eq!(summary(node("FILTER", "x > 1", 1, vec![])), "FILTER: x > 1");
+        assert_eq!(summary(node("CTE_SCAN", "t\n", 1, vec![])), "CTE_SCAN t");
+        assert_eq!(
+            summary(node("RECURSIVE_CTE", "t\n", 1, vec![])),
+            "RECURSIVE_CTE t"
+        );
+        assert_eq!(
+            summary(node("RESULT_COLLECTOR", "", 1, vec![])),
+            "RESULT_COLLECTOR"
+        );
+
+        let mut chain = parsed(inner_join(
+            &["b.id = c.id"],
+            1,
+            inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id"], 1),
+                scan("b", &["id"], 1),
+            ),
+            scan("c", &["id"], 1),
+        ));
+        rewrite::collapse_inner_joins(&mut chain);
+        assert_eq!(
+            chain.summary(),
+            "MULTIWAY_JOIN of 3 relations: b.id = c.id AND a.id = b.id"
+        );
+    }
+
+    #[test]
+    fn unknown_node_types_can_be_skipped() {
+        let plan = json(&node(
+            "PROJECTION",
+            "id",
+            1,
+            vec![node("QUANTUM_JOIN", "???", 1, vec![scan("a", &["id"], 1)])],
+        ));
+        assert!(matches!(
+            from_str(&plan).unwrap_err().downcast_ref::<ParseError>(),
+            Some(ParseError::UnknownNodeType(name)) if name == "QUANTUM_JOIN"
+        ));
+        let opts = ParseOptions {
+            on_unknown: UnknownPolicy::Skip,
+            ..Default::default()
+        };
+        let (root, skipped) = from_str_with_skipped(&plan, &opts).unwrap();
+        assert_eq!(skipped, vec!["QUANTUM_JOIN"]);
+        assert!(root.children[0].attr.is_none());
+        assert!(root.attr.is_some());
+        assert!(root.children[0].children[0].attr.is_some());
+    }
+
+    #[test]
+    fn parses_comparisons() {
+        let compare = |col: &str, op, right| Predicate::Compare {
+            left: Attribute::new("", col),
+            op,
+            right,
+        };
+        let literal = |s: &str| Value::Literal(s.to_string());
+        assert_eq!(
+            parse_predicate("x>=10"),
+            compare("x", CompareOp::Ge, literal("10"))
+        );
+        assert_eq!(
+            parse_predicate("x BETWEEN 1 AND 5"),
+            compare(
+                "x",
+                CompareOp::Between,
+                Value::List(vec![literal("1"), literal("5")])
+            )
+        );
+        assert_eq!(
+            parse_predicate("(a.x = b.y)"),
+            Predicate::Compare {
+                left: Attribute::new("a", "x"),
+                op: CompareOp::Eq,
+                right: Value::Attribute(Attribute::new("b", "y")),
+            }
+        );
+        assert_eq!(
+            parse_predicate("name LIKE '%(voice)%'"),
+            compare("name", CompareOp::Like, literal("'%(voice)%'"))
+        );
+        assert_eq!(
+            parse_predicate("(x + 1) > 3"),
+            Predicate::Raw("(x + 1) > 3".to_string())
+        );
+    }
+
+    #[test]
+    fn loads_each_statement_of_an_array() {
+        let file =
+            path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/two_statements.json");
+        let plans = load_all(&file).unwrap();
+        assert_eq!(plans.len(), 2);
+        assert_eq!(plans[0].children[0].name, "SEQ_SCAN");
+        assert_eq!(plans[1].children[0].name, "HASH_JOIN");
+        assert_eq!(plans[1].find(3).map(|n| n.cardinality), Some(10));
+
+        let single =
+            path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/in_subquery.json");
+        assert_eq!(load_all(&single).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn timings_normalized_from_either_unit() {
+        let profile = |timing| {
+            let mut root = node("Query", "", 0, vec![timed(scan("a", &["id"], 1), timing)]);
+            root.total_timing = Some(0.5);
+            root
+        };
+
+        let mut seconds = profile(0.3);
+        assert_eq!(TimeUnit::detect(&seconds), TimeUnit::Seconds);
+        normalize_timings(&mut seconds, None);
+        assert_eq!(seconds.children[0].timing, Some(0.3));
+
+        let mut millis = profile(300.0);
+        assert_eq!(TimeUnit::detect(&millis), TimeUnit::Milliseconds);
+        normalize_timings(&mut millis, None);
+        assert_eq!(millis.children[0].timing, Some(0.3));
+
+        let mut forced = profile(0.3);
+        normalize_timings(&mut forced, Some(TimeUnit::Milliseconds));
+        assert_eq!(forced.children[0].timing, Some(0.3 / 1000.0));
+    }
+
+    #[test]
+    fn unqualified_columns_follow_the_policy() {
+        let profile = json(&node("PROJECTION", "id", 1, vec![scan("a", &["id"], 1)]));
+        let projected = |policy| {
+            let opts = ParseOptions {
+                unqualified_table: policy,
+                ..Default::default()
+            };
+            let root = from_str_with(&profile, &opts).unwrap();
+            let Some(Op::Project(project)) = root.attr else {
+                panic!("expected a projection");
+            };
+            project.columns
+        };
+        assert_eq!(
+            projected(UnqualifiedPolicy::Empty),
+            vec![Attribute::new("", "id")]
+        );
+        assert_eq!(
+            projected(UnqualifiedPolicy::Deadbeef("deadbeef".to_string())),
+            vec![Attribute::new("deadbeef", "id")]
+        );
+        assert_eq!(
+            projected(UnqualifiedPolicy::ResolveFromScans),
+            vec![Attribute::new("a", "id")]
+        );
+    }
+
+    #[test]
+    fn semi_and_anti_joins_record_the_preserved_side() {
+        let join = |join_type: &str| {
+            let extra_info = format!("{}\na.id = b.id\n", join_type);
+            let plan = parsed(node(
+                "HASH_JOIN",
+                &extra_info,
+                1,
+                vec![scan("a", &["id"], 1), scan("b", &["id"], 1)],
+            ));
+            let join = plan.as_join().unwrap();
+            (join.join_type.clone(), join.preserved_side)
+        };
+        assert_eq!(join("SEMI"), (JoinType::Semi, Some(Side::Left)));
+        assert_eq!(join("RIGHT_ANTI"), (JoinType::Anti, Some(Side::Right)));
+    }
+
+    #[test]
+    fn from_reader_reads_a_cursor() {
+        let profile = json(&scan("a", &["id"], 7));
+        let root = from_reader(io::Cursor::new(profile.as_bytes())).unwrap();
+        assert_eq!(root.cardinality, 7);
+        assert_eq!(root.as_scan().unwrap().table_name, "a");
+    }
+
+    #[test]
+    fn parses_a_non_recursive_cte() {
+        let root = from_str(include_str!("../tests/data/cte.json")).unwrap();
+        let cte_scan = root.find(6).unwrap();
+        assert_eq!(cte_scan.name, "CTE_SCAN");
+        assert_eq!(
+            cte_scan.attr,
+            Some(Op::CteScan(CteScan {
+                name: "big".to_string()
+            }))
+        );
+        let graph = graph::JoinGraph::from_plan(&root);
+        assert!(graph.relation("big").is_some());
+        assert!(graph.relation("customer").is_some());
+        assert_eq!(graph.edges.len(), 1);
+    }
+
+    #[test]
+    fn scan_columns_with_and_without_types() {
+        let typed = parsed(scan(
+            "lineitem",
+            &["l_orderkey (INTEGER)", "l_comment (VARCHAR)"],
+            1,
+        ));
+        let untyped = parsed(scan("lineitem", &["l_orderkey", "l_comment"], 1));
+        let typed = &typed.as_scan().unwrap().attributes;
+        let untyped = &untyped.as_scan().unwrap().attributes;
+
+        assert_eq!(typed[0].attr_name, "l_orderkey");
+        assert_eq!(typed[0].data_type.as_deref(), Some("INTEGER"));
+        assert_eq!(typed[1].data_type.as_deref(), Some("VARCHAR"));
+        assert!(untyped.iter().all(|a| a.data_type.is_none()));
+        // the type takes no part in comparisons
+        assert_eq!(typed, untyped);
+    }
+
+    #[test]
+    fn absent_timing_differs_from_zero() {
+        let explained = from_str(&json(&scan("a", &["id"], 1))).unwrap();
+        let measured = from_str(&json(&timed(scan("a", &["id"], 1), 0.0))).unwrap();
+        assert_eq!(explained.timing, None);
+        assert_eq!(measured.timing, Some(0.0));
+        assert_eq!(explained.timing_or_zero(), measured.timing_or_zero());
+        assert!(!json(&explained).contains("\"timing\""));
+        assert!(json(&measured).contains("\"timing\":0.0"));
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn load_dir_matches_the_pattern() {
+        let dir = std::env::temp_dir().join(format!("ddbplan-load-dir-{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let profile = json(&scan("a", &["id"], 1));
+        fs::write(dir.join("q1.json"), &profile).unwrap();
+        fs::write(dir.join("q2.json"), "not json").unwrap();
+        fs::write(dir.join("notes.txt"), &profile).unwrap();
+        fs::write(dir.join("sub").join("q3.json"), &profile).unwrap();
+
+        let loaded = load_dir(&dir, "q*.json").unwrap();
+        let names: Vec<_> = loaded.iter().map(|(p, _)| p.file_name().unwrap()).collect();
+        assert_eq!(names, vec!["q1.json", "q2.json"]);
+        assert!(loaded[0].1.is_ok());
+        assert!(loaded[1].1.is_err());
+        assert_eq!(load_dir_recursive(&dir, "q*.json").unwrap().len(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ops_round_trip_with_a_kind_tag() {
+        let mut multiway = parsed(inner_join(
+            &["b.id = c.id"],
+            1,
+            inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id"], 1),
+                scan("b", &["id"], 1),
+            ),
+            scan("c", &["id"], 1),
+        ));
+        rewrite::collapse_inner_joins(&mut multiway);
+        let ops = [
+            (
+                "Join",
+                parsed(inner_join(
+                    &["a.id = b.id"],
+                    1,
+                    scan("a", &["id"], 1),
+                    scan("b", &["id"], 1),
+                )),
+            ),
+            ("Scan", parsed(scan("a", &["id"], 1))),
+            ("Project", parsed(node("PROJECTION", "a.id", 1, vec![]))),
+            (
+                "Aggregate",
+                parsed(node("HASH_GROUP_BY", "#0\ncount_star()", 1, vec![])),
+            ),
+            ("Filter", parsed(node("FILTER", "a.x > 3", 1, vec![]))),
+            ("MultiwayJoin", multiway),
+            ("CteScan", parsed(node("CTE_SCAN", "t", 1, vec![]))),
+            (
+                "RecursiveCte",
+                parsed(node("RECURSIVE_CTE", "t", 1, vec![])),
+            ),
+        ];
+        for (kind, node) in ops {
+            let op = node.attr.unwrap();
+            let value = serde_json::to_value(&op).unwrap();
+            assert_eq!(value["kind"], kind);
+            assert_eq!(serde_json::from_value::<Op>(value).unwrap(), op);
+        }
+    }
+
+    #[test]
+    fn text_and_sectioned_extra_info_parse_alike() {
+        fn ops(root: &Node) -> Vec<Option<Op>> {
+            let mut ops = Vec::new();
+            preorder_traverse(root, &mut |node: &Node| ops.push(node.attr.clone()));
+            ops
+        }
+        let text = from_str(include_str!("../tests/data/extra_info_text.json")).unwrap();
+        let sections = from_str(include_str!("../tests/data/extra_info_sections.json")).unwrap();
+        assert_eq!(ops(&text), ops(&sections));
+        assert_eq!(text.children[0].as_scan().unwrap().filters.len(), 1);
+    }
+
+    #[test]
+    fn structure_only_loads_unknown_node_types() {
+        let profile = json(&node(
+            "PROJECTION",
+            "a.id",
+            1,
+            vec![node(
+                "VECTORIZED_TELEPORT",
+                "{{ nonsense",
+                1,
+                vec![scan("a", &["id"], 1)],
+            )],
+        ));
+        assert!(from_str(&profile).is_err());
+        let root = from_str_structure_only(&profile).unwrap();
+        assert_eq!(root.children[0].name, "VECTORIZED_TELEPORT");
+        assert_eq!(root.children[0].children[0].id, 2);
+        assert!(root.attr.is_none() && root.children[0].children[0].attr.is_none());
+    }
+
+    #[test]
+    fn strictness_decides_about_a_malformed_node() {
+        let profile = json(&node(
+            "PROJECTION",
+            "a.id",
+            1,
+            vec![node(
+                "HASH_JOIN",
+                "SIDEWAYS\na.id = b.id\n",
+                1,
+                vec![scan("a", &["id"], 1), scan("b", &["id"], 1)],
+            )],
+        ));
+        let strict = ParseOptions::default();
+        assert!(from_str_report(&profile, &strict).is_err());
+
+        let lenient = ParseOptions {
+            strictness: Strictness::Lenient,
+            ..Default::default()
+        };
+        let report = from_str_report(&profile, &lenient).unwrap();
+        assert_eq!(
+            report.warnings,
+            vec![Warning {
+                node: 1,
+                error: ParseError::UnknownJoinType("SIDEWAYS".to_string()),
+            }]
+        );
+        assert!(report.warnings[0].to_string().starts_with("node 1: "));
+        assert!(report.tree.children[0].attr.is_none());
+        assert!(report.tree.children[0].children[0].is_scan());
+        assert!(report.tree.attr.is_some());
+    }
+
+    #[test]
+    fn accessors_match_the_parsed_op() {
+        let plan = parsed(node(
+            "HASH_GROUP_BY",
+            "#0\ncount_star()",
+            1,
+            vec![node(
+                "PROJECTION",
+                "a.id",
+                1,
+                vec![node(
+                    "FILTER",
+                    "a.x > 3",
+                    1,
+                    vec![inner_join(
+                        &["a.id = b.id"],
+                        1,
+                        scan("a", &["id", "x"], 1),
+                        scan("b", &["id"], 1),
+                    )],
+                )],
+            )],
+        ));
+        let (aggregate, projection) = (&plan, &*plan.children[0]);
+        let filter = &*projection.children[0];
+        let join = &*filter.children[0];
+        let scan = &*join.children[0];
+        let checks = |node: &Node| {
+            [
+                node.is_join(),
+                node.is_scan(),
+                node.is_projection(),
+                node.is_filter(),
+                node.is_aggregate(),
+            ]
+        };
+        assert_eq!(checks(aggregate), [false, false, false, false, true]);
+        assert_eq!(checks(projection), [false, false, true, false, false]);
+        assert_eq!(checks(filter), [false, false, false, true, false]);
+        assert_eq!(checks(join), [true, false, false, false, false]);
+        assert_eq!(checks(scan), [false, true, false, false, false]);
+
+        assert_eq!(aggregate.as_aggregate().unwrap().aggregates.len(), 1);
+        assert_eq!(projection.as_projection().unwrap().columns.len(), 1);
+        assert_eq!(filter.as_filter().unwrap().predicates.len(), 1);
+        assert_eq!(join.as_join().unwrap().equalizers.len(), 1);
+        assert_eq!(scan.as_scan().unwrap().table_name, "a");
+        assert!(scan.as_join().is_none() && join.as_scan().is_none());
+    }
+
+    #[test]
+    fn reparse_is_idempotent() {
+        let fresh = from_str(include_str!("../tests/data/in_subquery.json")).unwrap();
+        let mut reparsed = fresh.clone();
+        reparse(&mut reparsed).unwrap();
+        reparse(&mut reparsed).unwrap();
+        assert_trees_eq(&fresh, &reparsed);
+
+        let mut marks = Vec::new();
+        preorder_traverse(&reparsed, &mut |node: &Node| {
+            if let Some(join) = node.as_join() {
+                marks.extend(join.mark_column.clone());
+            }
+        });
+        assert_eq!(marks, vec![Attribute::new("SUBQUERY", "SUBQUERY")]);
+    }
+}
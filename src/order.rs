@@ -0,0 +1,685 @@
+use std::{collections::HashMap, error::Error, fmt};
+
+use crate::analysis::inner_join_core;
+use crate::cost::{
+    base_size, estimate_cardinality, learn_selectivities, plan_cost, selectivity, Selectivities,
+    DEFAULT_SELECTIVITY,
+};
+use crate::graph::{is_acyclic, weighted_join_graph, JoinEdge, JoinGraph, RelationId};
+use crate::{Condition, Node};
+
+/// Largest number of relations `order_quality` will enumerate; the DP is
+/// exponential (3^n) in this.
+pub const DEFAULT_DP_MAX_RELATIONS: usize = 12;
+
+/// Largest number of relations `enumerate_left_deep` accepts.
+pub const MAX_LEFT_DEEP_RELATIONS: usize = 10;
+
+/// Smallest estimated speedup, in percent, for which `suggest_reordering`
+/// suggests another order.
+pub const MIN_SUGGESTED_SPEEDUP: f64 = 5.0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TooManyRelations {
+    pub relations: usize,
+    pub max: usize,
+}
+
+impl fmt::Display for TooManyRelations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} relations is more than the {} that can be enumerated",
+            self.relations, self.max
+        )
+    }
+}
+
+impl Error for TooManyRelations {}
+
+/// All left-deep join orders of the relations in `graph`. Each order only
+/// adds a relation connected to the ones already joined, unless none of the
+/// remaining relations is connected (a cartesian product is then unavoidable).
+pub fn enumerate_left_deep(
+    graph: &JoinGraph,
+) -> Result<impl Iterator<Item = Vec<RelationId>>, TooManyRelations> {
+    if graph.len() > MAX_LEFT_DEEP_RELATIONS {
+        return Err(TooManyRelations {
+            relations: graph.len(),
+            max: MAX_LEFT_DEEP_RELATIONS,
+        });
+    }
+    Ok(LeftDeepOrders {
+        neighbors: (0..graph.len())
+            .map(|i| graph.neighbors(RelationId(i)))
+            .collect(),
+        stack: vec![Vec::new()],
+    })
+}
+
+struct LeftDeepOrders {
+    neighbors: Vec<Vec<RelationId>>,
+    // partial orders still to be extended, depth first
+    stack: Vec<Vec<RelationId>>,
+}
+
+impl Iterator for LeftDeepOrders {
+    type Item = Vec<RelationId>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let n = self.neighbors.len();
+        while let Some(prefix) = self.stack.pop() {
+            if prefix.len() == n {
+                return Some(prefix);
+            }
+            let remaining: Vec<_> = (0..n)
+                .map(RelationId)
+                .filter(|r| !prefix.contains(r))
+                .collect();
+            let connected: Vec<_> = remaining
+                .iter()
+                .copied()
+                .filter(|r| prefix.iter().any(|p| self.neighbors[p.0].contains(r)))
+                .collect();
+            let candidates = if prefix.is_empty() || connected.is_empty() {
+                remaining
+            } else {
+                connected
+            };
+            // pushed in reverse so that orders come out lexicographically
+            for next in candidates.into_iter().rev() {
+                let mut order = prefix.clone();
+                order.push(next);
+                self.stack.push(order);
+            }
+        }
+        None
+    }
+}
+
+/// Cost (sum of intermediate cardinalities) of the best bushy join tree over
+/// all relations of `graph`, found by dynamic programming over subsets.
+/// Returns `None` when the graph has more than `max_relations` relations.
+pub fn optimal_bushy_cost(
+    graph: &JoinGraph,
+    base_sizes: &HashMap<String, u64>,
+    selectivities: &Selectivities,
+    max_relations: usize,
+) -> Option<f64> {
+    let n = graph.len();
+    if n > max_relations || n >= usize::BITS as usize {
+        return None;
+    }
+    let full = (1usize << n) - 1;
+
+    // card[s] is built from card[s without its lowest relation]
+    let mut card = vec![1.0; full + 1];
+    for s in 1..=full {
+        let low = s.trailing_zeros() as usize;
+        let rest = s & (s - 1);
+        let mut c = card[rest] * base_size(graph, RelationId(low), base_sizes);
+        for e in &graph.edges {
+            let (l, r) = (e.left.0, e.right.0);
+            if (l == low && (rest >> r) & 1 == 1)
+                || (r == low && (rest >> l) & 1 == 1)
+                || (l == low && r == low)
+            {
+                c *= selectivity(&e.condition, selectivities);
+            }
+        }
+        card[s] = c;
+    }
+
+    let mut cost = vec![0.0; full + 1];
+    for s in 1..=full {
+        if s & (s - 1) == 0 {
+            continue;
+        }
+        let mut best = f64::INFINITY;
+        // enumerate proper subsets, visiting each split once
+        let mut left = (s - 1) & s;
+        while left > 0 {
+            let right = s ^ left;
+            if left < right {
+                best = best.min(cost[left] + cost[right]);
+            }
+            left = (left - 1) & s;
+        }
+        cost[s] = card[s] + best;
+    }
+    Some(cost[full])
+}
+
+/// Ratio of the estimated cost of DuckDB's join order to the cost of the
+/// optimal bushy order over the same relations; 1.0 means DuckDB found the
+/// optimum. `None` if the plan has no inner joins, too many relations, or an
+/// optimal cost of zero.
+pub fn order_quality(
+    root: &Node,
+    base_sizes: &HashMap<String, u64>,
+    selectivities: &Selectivities,
+) -> Option<f64> {
+    order_quality_with_limit(root, base_sizes, selectivities, DEFAULT_DP_MAX_RELATIONS)
+}
+
+pub fn order_quality_with_limit(
+    root: &Node,
+    base_sizes: &HashMap<String, u64>,
+    selectivities: &Selectivities,
+    max_relations: usize,
+) -> Option<f64> {
+    let graph = JoinGraph::from_plan(root);
+    if graph.len() < 2 {
+        return None;
+    }
+    let optimal = optimal_bushy_cost(&graph, base_sizes, selectivities, max_relations)?;
+    if optimal == 0.0 {
+        return None;
+    }
+    Some(plan_cost(root, base_sizes, selectivities) / optimal)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CyclicQuery;
+
+impl fmt::Display for CyclicQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "query is cyclic and has no join tree; consider a worst-case optimal join"
+        )
+    }
+}
+
+impl Error for CyclicQuery {}
+
+/// A left-deep join order that starts from the smallest relation and then
+/// repeatedly adds the connected relation keeping the running intermediate
+/// result smallest. Falls back to an unconnected relation only when no
+/// remaining one is connected.
+pub fn greedy_selectivity_order(
+    graph: &JoinGraph,
+    base_sizes: &HashMap<String, u64>,
+    selectivities: &Selectivities,
+) -> Vec<RelationId> {
+    let mut joined: Vec<RelationId> = Vec::new();
+    while joined.len() < graph.len() {
+        let remaining = (0..graph.len())
+            .map(RelationId)
+            .filter(|r| !joined.contains(r));
+        let connected: Vec<_> = remaining
+            .clone()
+            .filter(|r| joined.is_empty() || joined.iter().any(|j| graph.neighbors(*j).contains(r)))
+            .collect();
+        let candidates = if connected.is_empty() {
+            remaining.collect()
+        } else {
+            connected
+        };
+        let next = candidates
+            .into_iter()
+            .map(|r| {
+                let mut relations = joined.clone();
+                relations.push(r);
+                (
+                    r,
+                    estimate_cardinality(graph, &relations, base_sizes, selectivities),
+                )
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap()
+            .0;
+        joined.push(next);
+    }
+    joined
+}
+
+/// Estimated cost of joining `order` left-deep: the sum of the estimated
+/// cardinalities of each intermediate result, comparable with `plan_cost`.
+pub fn left_deep_cost(
+    graph: &JoinGraph,
+    order: &[RelationId],
+    base_sizes: &HashMap<String, u64>,
+    selectivities: &Selectivities,
+) -> f64 {
+    (2..=order.len())
+        .map(|n| estimate_cardinality(graph, &order[..n], base_sizes, selectivities))
+        .sum()
+}
+
+/// A join tree for an acyclic query, as the join conditions in the order
+/// they are applied, together with its estimated cost (sum of intermediate
+/// cardinalities). Relations are added as by `greedy_selectivity_order`.
+pub fn min_cost_join_tree(
+    graph: &JoinGraph,
+    base_sizes: &HashMap<String, u64>,
+    selectivities: &Selectivities,
+) -> Result<(Vec<Condition>, u64), CyclicQuery> {
+    if !is_acyclic(graph) {
+        return Err(CyclicQuery);
+    }
+    let order = greedy_selectivity_order(graph, base_sizes, selectivities);
+    let mut conditions = Vec::new();
+    for (i, next) in order.iter().enumerate() {
+        let joined = &order[..i];
+        conditions.extend(
+            graph
+                .edges
+                .iter()
+                .filter(|e| {
+                    (e.left == *next && joined.contains(&e.right))
+                        || (e.right == *next && joined.contains(&e.left))
+                })
+                .map(|e| e.condition.clone()),
+        );
+    }
+    let cost = left_deep_cost(graph, &order, base_sizes, selectivities);
+    Ok((conditions, cost.round() as u64))
+}
+
+/// Why `mst_join_order` has no order for a plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MstError {
+    Cyclic,
+    // some relations aren't connected to the others by any join condition
+    Disconnected,
+}
+
+impl fmt::Display for MstError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MstError::Cyclic => write!(f, "{}", CyclicQuery),
+            MstError::Disconnected => write!(f, "join graph is not connected"),
+        }
+    }
+}
+
+impl Error for MstError {}
+
+/// A join order for the plan's inner joins following a minimum spanning
+/// tree of its join graph, weighted by the observed selectivities of
+/// `weighted_join_graph` (`DEFAULT_SELECTIVITY` for pairs it has none for):
+/// starting from the most selective edge, the relation with the most
+/// selective edge to those already joined is added next, as in Prim's
+/// algorithm. Returned, like `min_cost_join_tree`, as the join conditions in
+/// the order they are applied. Only defined for connected, acyclic queries.
+pub fn mst_join_order(root: &Node) -> Result<Vec<Condition>, MstError> {
+    let graph = JoinGraph::from_plan(root);
+    if !graph.is_connected() {
+        return Err(MstError::Disconnected);
+    }
+    if !is_acyclic(&graph) {
+        return Err(MstError::Cyclic);
+    }
+    let weights = weighted_join_graph(root);
+    let weight = |e: &JoinEdge| {
+        let (left, right) = (graph.name(e.left), graph.name(e.right));
+        let pair = if left <= right {
+            (left.to_string(), right.to_string())
+        } else {
+            (right.to_string(), left.to_string())
+        };
+        weights.get(&pair).copied().unwrap_or(DEFAULT_SELECTIVITY)
+    };
+
+    let mut joined: Vec<RelationId> = Vec::new();
+    let mut applied = vec![false; graph.edges.len()];
+    let mut conditions = Vec::new();
+    while joined.len() < graph.len() {
+        // the most selective edge leaving the joined relations, or any edge
+        // to start with
+        let next = graph
+            .edges
+            .iter()
+            .filter(|e| joined.is_empty() || joined.contains(&e.left) != joined.contains(&e.right))
+            .min_by(|a, b| weight(a).total_cmp(&weight(b)));
+        let Some(edge) = next else {
+            // a single relation without conditions
+            break;
+        };
+        for r in [edge.left, edge.right] {
+            if !joined.contains(&r) {
+                joined.push(r);
+            }
+        }
+        for (i, e) in graph.edges.iter().enumerate() {
+            if !applied[i] && joined.contains(&e.left) && joined.contains(&e.right) {
+                applied[i] = true;
+                conditions.push(e.condition.clone());
+            }
+        }
+    }
+    Ok(conditions)
+}
+
+/// A join order estimated to be cheaper than the plan's own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReorderSuggestion {
+    /// Tables in the order to join them left-deep.
+    pub order: Vec<String>,
+    /// Estimated cost of the plan's own order, see `plan_cost`.
+    pub current_cost: f64,
+    /// Estimated cost of `order`, see `left_deep_cost`.
+    pub suggested_cost: f64,
+    /// How much faster `order` is estimated to be, in percent:
+    /// `current_cost / suggested_cost - 1`, times 100.
+    pub speedup_percent: f64,
+}
+
+// the cheapest left-deep order of `graph` as searched by `suggest_reordering`,
+// with its estimated cost
+fn cheapest_order(
+    graph: &JoinGraph,
+    base_sizes: &HashMap<String, u64>,
+    selectivities: &Selectivities,
+) -> Option<(Vec<RelationId>, f64)> {
+    let cost = |order: &[RelationId]| left_deep_cost(graph, order, base_sizes, selectivities);
+    match enumerate_left_deep(graph) {
+        Ok(orders) => orders
+            .map(|order| {
+                let c = cost(&order);
+                (order, c)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1)),
+        Err(TooManyRelations { .. }) => {
+            let order = greedy_selectivity_order(graph, base_sizes, selectivities);
+            let c = cost(&order);
+            Some((order, c))
+        }
+    }
+}
+
+/// The tables of the plan's inner-join core in the cheapest left-deep order
+/// found as by `suggest_reordering`, whether or not it beats DuckDB's. `None`
+/// if there is no inner join.
+pub fn cheapest_left_deep_order(root: &Node) -> Option<Vec<String>> {
+    let core = inner_join_core(root)?;
+    let graph = JoinGraph::from_plan(core);
+    let (order, _) = cheapest_order(&graph, &HashMap::new(), &learn_selectivities(core))?;
+    Some(order.iter().map(|&r| graph.name(r).to_string()).collect())
+}
+
+/// Looks for a better order for the plan's inner-join core (see
+/// `inner_join_core`), estimating costs with the selectivities learned from
+/// the plan itself (see `learn_selectivities`) and the observed scan
+/// cardinalities. All left-deep orders are tried when there are at most
+/// `MAX_LEFT_DEEP_RELATIONS` relations, the greedy order otherwise. `None` if
+/// there is no inner join or the best order found is less than
+/// `MIN_SUGGESTED_SPEEDUP` percent faster than DuckDB's.
+pub fn suggest_reordering(root: &Node) -> Option<ReorderSuggestion> {
+    let core = inner_join_core(root)?;
+    let graph = JoinGraph::from_plan(core);
+    let base_sizes = HashMap::new();
+    let selectivities = learn_selectivities(core);
+    let (order, suggested_cost) = cheapest_order(&graph, &base_sizes, &selectivities)?;
+    let current_cost = plan_cost(core, &base_sizes, &selectivities);
+    if suggested_cost <= 0.0 {
+        return None;
+    }
+    let speedup_percent = (current_cost / suggested_cost - 1.0) * 100.0;
+    if speedup_percent < MIN_SUGGESTED_SPEEDUP {
+        return None;
+    }
+    Some(ReorderSuggestion {
+        order: order.iter().map(|&r| graph.name(r).to_string()).collect(),
+        current_cost,
+        suggested_cost,
+        speedup_percent,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+
+    #[test]
+    fn left_deep_orders_of_a_path() {
+        // a - b - c
+        let plan = parsed(inner_join(
+            &["b.id = c.id"],
+            1,
+            inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id"], 1),
+                scan("b", &["id"], 1),
+            ),
+            scan("c", &["id"], 1),
+        ));
+        let graph = JoinGraph::from_plan(&plan);
+        let orders: Vec<Vec<&str>> = enumerate_left_deep(&graph)
+            .unwrap()
+            .map(|order| order.iter().map(|&r| graph.name(r)).collect())
+            .collect();
+        assert_eq!(orders.len(), 4);
+        assert!(!orders.contains(&vec!["a", "c", "b"]));
+    }
+
+    #[test]
+    fn left_deep_orders_include_a_cross_joined_relation() {
+        // (a - b) x c
+        let plan = parsed(node(
+            "HASH_JOIN",
+            "INNER\n",
+            1,
+            vec![
+                inner_join(
+                    &["a.id = b.id"],
+                    1,
+                    scan("a", &["id"], 1),
+                    scan("b", &["id"], 1),
+                ),
+                scan("c", &["id"], 1),
+            ],
+        ));
+        let graph = JoinGraph::from_plan(&plan);
+        let orders: Vec<Vec<&str>> = enumerate_left_deep(&graph)
+            .unwrap()
+            .map(|order| order.iter().map(|&r| graph.name(r)).collect())
+            .collect();
+        // c only comes first or after both of a and b
+        assert_eq!(
+            orders,
+            vec![
+                vec!["a", "b", "c"],
+                vec!["b", "a", "c"],
+                vec!["c", "a", "b"],
+                vec!["c", "b", "a"],
+            ]
+        );
+    }
+
+    // a - b - c, with b the smallest relation
+    fn chain() -> Node {
+        parsed(inner_join(
+            &["b.id = c.id"],
+            1,
+            inner_join(
+                &["a.id = b.id"],
+                1,
+                scan("a", &["id"], 1000),
+                scan("b", &["id"], 10),
+            ),
+            scan("c", &["id"], 100),
+        ))
+    }
+
+    // a, b and c all joined with each other on different columns
+    fn triangle() -> Node {
+        parsed(inner_join(
+            &["b.y = c.y", "c.z = a.z"],
+            1,
+            inner_join(
+                &["a.x = b.x"],
+                1,
+                scan("a", &["x", "z"], 1),
+                scan("b", &["x", "y"], 1),
+            ),
+            scan("c", &["y", "z"], 1),
+        ))
+    }
+
+    #[test]
+    fn min_cost_join_tree_of_a_chain() {
+        let graph = JoinGraph::from_plan(&chain());
+        let (conditions, cost) =
+            min_cost_join_tree(&graph, &HashMap::new(), &Selectivities::default()).unwrap();
+        // b ⋈ c (100 tuples) first, then a (10000)
+        let tables: Vec<_> = conditions
+            .iter()
+            .map(|c| {
+                let mut pair = [
+                    c.left_attr.table_name.as_str(),
+                    c.right_attr.table_name.as_str(),
+                ];
+                pair.sort();
+                pair
+            })
+            .collect();
+        assert_eq!(tables, vec![["b", "c"], ["a", "b"]]);
+        assert_eq!(cost, 10100);
+    }
+
+    #[test]
+    fn min_cost_join_tree_rejects_cycles() {
+        let graph = JoinGraph::from_plan(&triangle());
+        assert_eq!(
+            min_cost_join_tree(&graph, &HashMap::new(), &Selectivities::default()),
+            Err(CyclicQuery)
+        );
+    }
+
+    #[test]
+    fn greedy_order_beats_the_plan_order() {
+        let graph = JoinGraph::from_plan(&chain());
+        let (sizes, selectivities) = (HashMap::new(), Selectivities::default());
+        let greedy = greedy_selectivity_order(&graph, &sizes, &selectivities);
+        let names: Vec<_> = greedy.iter().map(|&r| graph.name(r)).collect();
+        assert_eq!(names, vec!["b", "c", "a"]);
+
+        let naive: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|t| graph.relation(t).unwrap())
+            .collect();
+        assert!(
+            left_deep_cost(&graph, &greedy, &sizes, &selectivities)
+                < left_deep_cost(&graph, &naive, &sizes, &selectivities)
+        );
+    }
+
+    #[test]
+    fn suggest_reordering_finds_the_selective_join_first() {
+        // a ⋈ b keeps all of a, while b ⋈ c keeps a single row
+        let plan = parsed(inner_join(
+            &["b.id = c.id"],
+            100,
+            inner_join(
+                &["a.id = b.id"],
+                1000,
+                scan("a", &["id"], 1000),
+                scan("b", &["id"], 10),
+            ),
+            scan("c", &["id"], 100),
+        ));
+        let suggestion = suggest_reordering(&plan).unwrap();
+        assert_eq!(suggestion.order[2], "a");
+        assert!((suggestion.current_cost - 1100.0).abs() < 1e-6);
+        assert!((suggestion.suggested_cost - 101.0).abs() < 1e-6);
+        assert!(suggestion.speedup_percent > 900.0);
+
+        let reordered = parsed(inner_join(
+            &["a.id = b.id"],
+            100,
+            inner_join(
+                &["b.id = c.id"],
+                1,
+                scan("b", &["id"], 10),
+                scan("c", &["id"], 100),
+            ),
+            scan("a", &["id"], 1000),
+        ));
+        assert_eq!(suggest_reordering(&reordered), None);
+    }
+
+    #[test]
+    fn mst_join_order_starts_from_the_most_selective_join() {
+        // DuckDB joins a ⋈ b (selectivity 0.1) before b ⋈ c (0.001)
+        let plan = parsed(inner_join(
+            &["b.id = c.id"],
+            100,
+            inner_join(
+                &["a.id = b.id"],
+                1000,
+                scan("a", &["id"], 1000),
+                scan("b", &["id"], 10),
+            ),
+            scan("c", &["id"], 100),
+        ));
+        let conditions: Vec<_> = mst_join_order(&plan)
+            .unwrap()
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(conditions, vec!["b.id = c.id", "a.id = b.id"]);
+
+        let graph = JoinGraph::from_plan(&plan);
+        let (sizes, learned) = (HashMap::new(), learn_selectivities(&plan));
+        let mst: Vec<_> = ["b", "c", "a"]
+            .iter()
+            .map(|t| graph.relation(t).unwrap())
+            .collect();
+        let mst_cost = left_deep_cost(&graph, &mst, &sizes, &learned);
+        let duckdb_cost = plan_cost(&plan, &sizes, &learned);
+        assert!((duckdb_cost - mst_cost - 999.0).abs() < 1e-6);
+
+        assert_eq!(mst_join_order(&triangle()), Err(MstError::Cyclic));
+    }
+
+    #[test]
+    fn mst_join_order_of_a_cross_product() {
+        // (a - b) x c
+        let plan = parsed(node(
+            "HASH_JOIN",
+            "INNER\n",
+            1,
+            vec![
+                inner_join(
+                    &["a.id = b.id"],
+                    1,
+                    scan("a", &["id"], 1),
+                    scan("b", &["id"], 1),
+                ),
+                scan("c", &["id"], 1),
+            ],
+        ));
+        assert_eq!(mst_join_order(&plan), Err(MstError::Disconnected));
+    }
+
+    #[test]
+    fn optimal_bushy_cost_of_a_chain() {
+        // with the default selectivity of 0.1: a ⋈ b has 1000 tuples, b ⋈ c
+        // 100, a × c 100000 and all three 10000, so b ⋈ c first is best
+        let graph = JoinGraph::from_plan(&chain());
+        let cost = optimal_bushy_cost(&graph, &HashMap::new(), &Selectivities::default(), 3);
+        assert_eq!(cost, Some(100.0 + 10000.0));
+    }
+
+    #[test]
+    fn order_quality_of_a_bad_order() {
+        // chain() joins a ⋈ b (1000 tuples) before adding c (10000)
+        let quality = order_quality(&chain(), &HashMap::new(), &Selectivities::default()).unwrap();
+        assert_eq!(quality, 11000.0 / 10100.0);
+        assert!(quality > 1.0);
+    }
+
+    #[test]
+    fn order_quality_gives_up_above_the_limit() {
+        let (sizes, selectivities) = (HashMap::new(), Selectivities::default());
+        assert!(order_quality_with_limit(&chain(), &sizes, &selectivities, 3).is_some());
+        assert_eq!(
+            order_quality_with_limit(&chain(), &sizes, &selectivities, 2),
+            None
+        );
+    }
+}
@@ -0,0 +1,59 @@
+//! Profiling queries with DuckDB, behind the `duckdb` feature (or
+//! `duckdb-bundled` to build DuckDB from source instead of linking it).
+
+use std::error::Error;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use duckdb::Connection;
+
+use crate::{from_json_auto, Node};
+
+static PROFILE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Runs `sql` on `conn` with JSON profiling enabled and parses the profile
+/// DuckDB writes for it. Profiling is disabled again afterwards.
+pub fn profile_query(conn: &Connection, sql: &str) -> Result<Node, Box<dyn Error>> {
+    let path = std::env::temp_dir().join(format!(
+        "ddbplan-{}-{}.json",
+        std::process::id(),
+        PROFILE_COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    conn.execute_batch(&format!(
+        "PRAGMA enable_profiling='json'; PRAGMA profiling_output='{}';",
+        path.display().to_string().replace('\'', "''")
+    ))?;
+    let result = (|| -> Result<(), duckdb::Error> {
+        let mut stmt = conn.prepare(sql)?;
+        let mut rows = stmt.query([])?;
+        while rows.next()?.is_some() {}
+        Ok(())
+    })();
+    let profile = (|| -> Result<String, Box<dyn Error>> {
+        conn.execute_batch("PRAGMA disable_profiling;")?;
+        result?;
+        Ok(fs::read_to_string(&path)?)
+    })();
+    // DuckDB may have written the profile before the query failed
+    let _ = fs::remove_file(&path);
+    from_json_auto(&profile?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::base_tables;
+
+    #[test]
+    fn profiles_a_join_in_memory() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE a AS SELECT range AS id FROM range(100);
+             CREATE TABLE b AS SELECT range AS id FROM range(10);",
+        )
+        .unwrap();
+        let plan = profile_query(&conn, "SELECT count(*) FROM a, b WHERE a.id = b.id").unwrap();
+        let tables: Vec<_> = base_tables(&plan).into_iter().collect();
+        assert_eq!(tables, vec!["a", "b"]);
+    }
+}
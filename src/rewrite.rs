@@ -0,0 +1,721 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::analysis::{available_attributes, base_tables, is_inner_join, referenced_columns};
+use crate::{
+    find_compare_op, inorder_traverse_mut, AggregateExpr, Attribute, CompareOp, Condition,
+    MultiwayJoin, Node, NodeId, Op, Predicate, RelationRef, Value,
+};
+
+/// Applies `f` to every attribute in the tree: scan attributes, join
+/// conditions and marker columns, projection columns, and the columns of
+/// parsed filter predicates.
+pub fn map_attributes(root: &mut Node, f: impl Fn(&mut Attribute)) {
+    inorder_traverse_mut(root, &mut |node: &mut Node| map_node_attributes(node, &f));
+}
+
+fn map_node_attributes(node: &mut Node, f: &impl Fn(&mut Attribute)) {
+    match &mut node.attr {
+        Some(Op::Join(join)) => {
+            for cond in &mut join.equalizers {
+                f(&mut cond.left_attr);
+                f(&mut cond.right_attr);
+            }
+            if let Some(mark) = &mut join.mark_column {
+                f(mark);
+            }
+        }
+        Some(Op::Scan(scan)) => {
+            scan.attributes.iter_mut().for_each(f);
+            scan.filters.iter_mut().for_each(|p| map_predicate(p, f));
+        }
+        Some(Op::Project(project)) => project.columns.iter_mut().for_each(f),
+        Some(Op::Aggregate(aggregate)) => {
+            aggregate.groups.iter_mut().for_each(f);
+            for expr in &mut aggregate.aggregates {
+                if let AggregateExpr::Func(func) = expr {
+                    func.args.iter_mut().for_each(f);
+                }
+            }
+        }
+        Some(Op::MultiwayJoin(multiway)) => {
+            for cond in &mut multiway.conditions {
+                f(&mut cond.left_attr);
+                f(&mut cond.right_attr);
+            }
+        }
+        Some(Op::Filter(filter)) => filter
+            .predicates
+            .iter_mut()
+            .for_each(|p| map_predicate(p, f)),
+        Some(Op::CteScan(_)) | Some(Op::RecursiveCte(_)) | None => {}
+    }
+}
+
+/// Renames tables throughout the tree according to `aliases` (e.g. a
+/// generated alias `t1` to `orders`): the tables of all attributes as in
+/// `map_attributes`, scanned tables, and the relations of multiway joins.
+/// Tables missing from `aliases` are left alone.
+pub fn apply_schema_aliases(root: &mut Node, aliases: &HashMap<String, String>) {
+    let rename = |table: &mut String| {
+        if let Some(name) = aliases.get(table) {
+            *table = name.clone();
+        }
+    };
+    inorder_traverse_mut(root, &mut |node: &mut Node| {
+        map_node_attributes(node, &|attr: &mut Attribute| rename(&mut attr.table_name));
+        match &mut node.attr {
+            Some(Op::Scan(scan)) => rename(&mut scan.table_name),
+            Some(Op::MultiwayJoin(multiway)) => multiway
+                .relations
+                .iter_mut()
+                .for_each(|r| rename(&mut r.table_name)),
+            _ => {}
+        }
+    });
+}
+
+/// Fills in the table of unqualified attributes (`table_name` empty) with
+/// the only table scanned below the attribute's node that has a column of
+/// that name. Positional references such as `#2`, and names read from no or
+/// several tables, stay unqualified.
+pub fn resolve_unqualified(root: &mut Node) {
+    // returns the tables scanned below `node` for each column name
+    fn resolve(node: &mut Node) -> HashMap<String, BTreeSet<String>> {
+        let mut columns: HashMap<String, BTreeSet<String>> = HashMap::new();
+        for child in &mut node.children {
+            for (name, tables) in resolve(child) {
+                columns.entry(name).or_default().extend(tables);
+            }
+        }
+        if let Some(Op::Scan(scan)) = &node.attr {
+            for attr in &scan.attributes {
+                columns
+                    .entry(attr.attr_name.clone())
+                    .or_default()
+                    .insert(scan.table_name.clone());
+            }
+        }
+        map_node_attributes(node, &|attr: &mut Attribute| {
+            if !attr.table_name.is_empty() {
+                return;
+            }
+            if let Some(tables) = columns.get(&attr.attr_name) {
+                if tables.len() == 1 {
+                    attr.table_name = tables.first().unwrap().clone();
+                }
+            }
+        });
+        columns
+    }
+    resolve(root);
+}
+
+fn map_predicate(predicate: &mut Predicate, f: &impl Fn(&mut Attribute)) {
+    fn map_value(value: &mut Value, f: &impl Fn(&mut Attribute)) {
+        match value {
+            Value::Attribute(attr) => f(attr),
+            Value::List(values) => values.iter_mut().for_each(|v| map_value(v, f)),
+            Value::Literal(_) => {}
+        }
+    }
+    if let Predicate::Compare { left, right, .. } = predicate {
+        f(left);
+        map_value(right, f);
+    }
+}
+
+// the sum of two timings, unmeasured only if neither was measured
+fn add_timings(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        _ => a.or(b),
+    }
+}
+
+/// Converts cumulative timings, where each node's `timing` includes that of
+/// its subtree, to the time spent in the node itself by subtracting the
+/// timings of its children. Unmeasured timings stay `None` and count as 0.
+/// A timing that would become negative is set to 0; returns how many were.
+pub fn to_self_timing(root: &mut Node) -> usize {
+    // returns the node's cumulative timing
+    fn convert(node: &mut Node, clamped: &mut usize) -> f64 {
+        let cumulative = node.timing_or_zero();
+        let children: f64 = node.children.iter_mut().map(|c| convert(c, clamped)).sum();
+        if let Some(timing) = &mut node.timing {
+            *timing -= children;
+            if *timing < 0.0 {
+                *timing = 0.0;
+                *clamped += 1;
+            }
+        }
+        cumulative
+    }
+    let mut clamped = 0;
+    convert(root, &mut clamped);
+    clamped
+}
+
+/// Replaces every maximal subtree of two or more directly nested inner joins
+/// with a single `MULTIWAY_JOIN` node over the subtree's inputs, keeping all
+/// of its join conditions. The new node takes the id and cardinality of the
+/// topmost join and the summed timing of the joins it replaces; its
+/// `extra_info` lists the conditions, and re-parsing the tree is no longer
+/// possible.
+pub fn collapse_inner_joins(root: &mut Node) {
+    // moves the join conditions out of an inner join
+    fn take_conditions(node: &mut Node, conditions: &mut Vec<Condition>) {
+        if let Some(Op::Join(join)) = &mut node.attr {
+            conditions.append(&mut join.equalizers);
+        }
+    }
+
+    let nested = root.children.iter().any(|c| is_inner_join(c));
+    if is_inner_join(root) && nested {
+        let mut inputs = Vec::new();
+        let mut conditions = Vec::new();
+        let mut timing = root.timing;
+        take_conditions(root, &mut conditions);
+        // walk the region depth first, left to right
+        let mut stack: Vec<_> = std::mem::take(&mut root.children);
+        stack.reverse();
+        while let Some(mut node) = stack.pop() {
+            if is_inner_join(&node) {
+                take_conditions(&mut node, &mut conditions);
+                timing = add_timings(timing, node.timing);
+                stack.extend(std::mem::take(&mut node.children).into_iter().rev());
+            } else {
+                inputs.push(node);
+            }
+        }
+        let relations = inputs
+            .iter()
+            .flat_map(|input| base_tables(input))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|table_name| RelationRef { table_name })
+            .collect();
+        root.name = "MULTIWAY_JOIN".to_string();
+        root.timing = timing;
+        root.extra_info = conditions.iter().map(|c| format!("{}\n", c)).collect();
+        root.attr = Some(Op::MultiwayJoin(MultiwayJoin {
+            relations,
+            conditions,
+        }));
+        root.children = inputs;
+    }
+    for child in &mut root.children {
+        collapse_inner_joins(child);
+    }
+}
+
+/// Marks every `HASH_JOIN` directly below a `HASH_GROUP_BY` that groups by
+/// join keys only as `followed_by_aggregate`, so that the pair can be
+/// treated as one grouped join. A group column the profile left unqualified
+/// matches a key of that name from any table. With `collapse`, the group by
+/// is also removed and the join takes over its cardinality, and its timing
+/// added to the join's; otherwise the tree keeps its shape.
+pub fn fuse_join_aggregate(root: &mut Node, collapse: bool) {
+    for child in &mut root.children {
+        fuse_join_aggregate(child, collapse);
+    }
+    let fusable = match (&root.attr, root.children.as_slice()) {
+        (Some(Op::Aggregate(aggregate)), [child])
+            if root.name == "HASH_GROUP_BY" && child.name == "HASH_JOIN" =>
+        {
+            match &child.attr {
+                Some(Op::Join(join)) => {
+                    let is_key = |group: &Attribute| {
+                        join.equalizers
+                            .iter()
+                            .flat_map(|c| [&c.left_attr, &c.right_attr])
+                            .any(|key| match group.table_name.as_str() {
+                                "" => key.attr_name == group.attr_name,
+                                _ => key == group,
+                            })
+                    };
+                    !aggregate.groups.is_empty() && aggregate.groups.iter().all(is_key)
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    };
+    if !fusable {
+        return;
+    }
+
+    if let Some(Op::Join(join)) = &mut root.children[0].attr {
+        join.followed_by_aggregate = true;
+    }
+    if collapse {
+        let mut join_node = root.children.pop().unwrap();
+        join_node.cardinality = root.cardinality;
+        join_node.timing = add_timings(join_node.timing, root.timing);
+        *root = *join_node;
+    }
+}
+
+// the column references of a predicate, as far as they can be told
+fn predicate_columns(predicate: &Predicate) -> Vec<Attribute> {
+    fn value_columns(value: &Value, columns: &mut Vec<Attribute>) {
+        match value {
+            Value::Attribute(attr) => columns.push(attr.clone()),
+            Value::List(values) => values.iter().for_each(|v| value_columns(v, columns)),
+            Value::Literal(_) => {}
+        }
+    }
+    match predicate {
+        Predicate::Compare { left, right, .. } => {
+            let mut columns = vec![left.clone()];
+            value_columns(right, &mut columns);
+            columns
+        }
+        Predicate::Raw(raw) => referenced_columns(raw),
+    }
+}
+
+/// Moves the predicates of every `FILTER` whose only child is a `SEQ_SCAN`
+/// into the scan's `filters` and removes the filter, as if the optimizer had
+/// pushed it down. The scan takes over the filter's cardinality. A filter
+/// referring to any column the scan doesn't read, including one qualified by
+/// another table, is left in place, as is one above an `INDEX_SCAN`.
+pub fn fuse_scan_filters(root: &mut Node) {
+    for child in &mut root.children {
+        fuse_scan_filters(child);
+    }
+    let fusable = match (&root.attr, root.children.as_slice()) {
+        (Some(Op::Filter(filter)), [child]) if child.name == "SEQ_SCAN" => match &child.attr {
+            Some(Op::Scan(scan)) => filter.predicates.iter().all(|p| {
+                predicate_columns(p).iter().all(|c| {
+                    (c.table_name.is_empty() || c.table_name == scan.table_name)
+                        && scan.attributes.iter().any(|a| a.attr_name == c.attr_name)
+                })
+            }),
+            _ => false,
+        },
+        _ => false,
+    };
+    if !fusable {
+        return;
+    }
+
+    let mut scan_node = root.children.pop().unwrap();
+    if let (Some(Op::Filter(filter)), Some(Op::Scan(scan))) =
+        (root.attr.take(), &mut scan_node.attr)
+    {
+        for mut predicate in filter.predicates {
+            // qualify by the scanned table, like the scan's own filters
+            if let Predicate::Compare { left, .. } = &mut predicate {
+                left.table_name = scan.table_name.clone();
+            }
+            scan.filters.push(predicate);
+        }
+    }
+    scan_node.cardinality = root.cardinality;
+    scan_node.timing = add_timings(scan_node.timing, root.timing);
+    *root = *scan_node;
+}
+
+/// A copy of the plan cut down to the operators that carry `col` to the
+/// output: every node whose output has it (see `available_attributes`),
+/// keeping only the children that have it too. The operator that produced
+/// the column, such as its scan, keeps its whole subtree, so a join keeps
+/// just the input the column comes from. Such a join has one input but still
+/// its conditions on both, so the slice is for display, not a plan to run or
+/// rewrite further. `None` if the plan doesn't output `col`.
+pub fn slice_for_column(root: &Node, col: &Attribute) -> Option<Node> {
+    fn slice(
+        node: &Node,
+        col: &Attribute,
+        available: &BTreeMap<NodeId, BTreeSet<Attribute>>,
+    ) -> Node {
+        let carriers: Vec<_> = node
+            .children
+            .iter()
+            .filter(|c| available[&c.id].contains(col))
+            .collect();
+        if carriers.is_empty() {
+            return node.clone();
+        }
+        node.with_children(
+            carriers
+                .into_iter()
+                .map(|c| Box::new(slice(c, col, available)))
+                .collect(),
+        )
+    }
+    let available = available_attributes(root);
+    if !available[&root.id].contains(col) {
+        return None;
+    }
+    Some(slice(root, col, &available))
+}
+
+// the attribute as it would appear in a profile, with the parts that
+// wouldn't read back as one identifier double quoted
+fn quoted(attr: &Attribute) -> String {
+    fn part(name: &str) -> String {
+        if name.contains(['.', '"']) || name.contains(char::is_whitespace) {
+            format!("\"{}\"", name.replace('"', "\"\""))
+        } else {
+            name.to_string()
+        }
+    }
+    if attr.table_name.is_empty() {
+        part(&attr.attr_name)
+    } else {
+        format!("{}.{}", part(&attr.table_name), part(&attr.attr_name))
+    }
+}
+
+/// Rewrites the tree into a canonical form, so that plans differing only in
+/// the order of commutative inputs become equal: the children of inner
+/// joins, multiway joins and unions are sorted by the tables scanned below
+/// them, join conditions are canonicalized and sorted (and `extra_info`
+/// rewritten to match), and `\r\n` line endings in `extra_info` become `\n`.
+/// Sorting forgets which input of a hash join was the build side.
+pub fn canonicalize(root: &mut Node) {
+    for child in &mut root.children {
+        canonicalize(child);
+    }
+    root.extra_info = root.extra_info.replace("\r\n", "\n");
+    let sort_conditions = |conditions: &mut Vec<Condition>| {
+        for cond in conditions.iter_mut() {
+            *cond = cond.canonical();
+        }
+        conditions.sort();
+        conditions
+            .iter()
+            .map(|c| format!("{} = {}\n", quoted(&c.left_attr), quoted(&c.right_attr)))
+            .collect::<String>()
+    };
+    match &mut root.attr {
+        Some(Op::Join(join)) => {
+            // the join type line, the conditions as DuckDB writes them, then
+            // any other line (non-equi conditions, a mark join's marker) as
+            // it was
+            let mut lines = root.extra_info.lines();
+            let join_type = lines.next().unwrap_or_default();
+            let others: String = lines
+                .filter(|l| !l.trim().is_empty())
+                .filter(|l| !matches!(find_compare_op(l), Some((_, _, Some(CompareOp::Eq)))))
+                .map(|l| format!("{}\n", l))
+                .collect();
+            root.extra_info = format!(
+                "{}\n{}{}",
+                join_type,
+                sort_conditions(&mut join.equalizers),
+                others
+            );
+        }
+        Some(Op::MultiwayJoin(multiway)) => {
+            root.extra_info = sort_conditions(&mut multiway.conditions);
+        }
+        _ => {}
+    }
+    let commutative = is_inner_join(root)
+        || matches!(root.attr, Some(Op::MultiwayJoin(_)))
+        || root.name == "UNION";
+    if commutative {
+        root.children.sort_by_cached_key(|c| base_tables(c));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_tree_extra_info;
+    use crate::test_support::*;
+
+    #[test]
+    fn map_attributes_renames_scans_and_conditions() {
+        let mut plan = parsed(inner_join(
+            &["t1.id = b.id"],
+            1,
+            scan("t1", &["id"], 1),
+            scan("b", &["id"], 1),
+        ));
+        map_attributes(&mut plan, |attr| {
+            if attr.table_name == "t1" {
+                attr.table_name = "orders".to_string();
+            }
+        });
+        let Some(Op::Join(join)) = &plan.attr else {
+            panic!("expected a join");
+        };
+        assert_eq!(join.equalizers[0].left_attr, Attribute::new("orders", "id"));
+        assert_eq!(join.equalizers[0].right_attr, Attribute::new("b", "id"));
+        let Some(Op::Scan(scan)) = &plan.children[0].attr else {
+            panic!("expected a scan");
+        };
+        assert_eq!(scan.attributes, vec![Attribute::new("orders", "id")]);
+    }
+
+    #[test]
+    fn collapse_inner_joins_flattens_a_chain() {
+        // ((a ⋈ b) ⋈ c) ⋈ d
+        let mut plan = parsed(inner_join(
+            &["c.id = d.id"],
+            2,
+            inner_join(
+                &["b.id = c.id"],
+                3,
+                inner_join(
+                    &["a.id = b.id"],
+                    4,
+                    scan("a", &["id"], 10),
+                    scan("b", &["id"], 10),
+                ),
+                scan("c", &["id"], 10),
+            ),
+            scan("d", &["id"], 10),
+        ));
+        collapse_inner_joins(&mut plan);
+        assert_eq!(plan.name, "MULTIWAY_JOIN");
+        assert_eq!(plan.cardinality, 2);
+        let inputs: Vec<_> = plan.children.iter().flat_map(|c| base_tables(c)).collect();
+        assert_eq!(inputs, vec!["a", "b", "c", "d"]);
+        let Some(Op::MultiwayJoin(join)) = &plan.attr else {
+            panic!("expected a multiway join");
+        };
+        let conditions: Vec<_> = join.conditions.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            conditions,
+            vec!["c.id = d.id", "b.id = c.id", "a.id = b.id"]
+        );
+        assert_eq!(join.relations.len(), 4);
+        assert_eq!(plan.extra_info, "c.id = d.id\nb.id = c.id\na.id = b.id\n");
+    }
+
+    #[test]
+    fn fuse_scan_filters_pushes_into_seq_scans() {
+        let mut plan = parsed(node("FILTER", "x > 3", 5, vec![scan("a", &["x"], 10)]));
+        fuse_scan_filters(&mut plan);
+        assert_eq!(plan.name, "SEQ_SCAN");
+        assert_eq!(plan.cardinality, 5);
+        let scan = plan.as_scan().unwrap();
+        assert_eq!(scan.filters.len(), 1);
+        let Predicate::Compare { left, .. } = &scan.filters[0] else {
+            panic!("expected a comparison");
+        };
+        assert_eq!(*left, Attribute::new("a", "x"));
+    }
+
+    #[test]
+    fn fuse_scan_filters_leaves_unfusable_filters() {
+        let index_scan = node("INDEX_SCAN", "a\n[INFOSEPARATOR]\nx", 10, vec![]);
+        for input in [
+            node("FILTER", "y > 3", 5, vec![scan("a", &["x"], 10)]),
+            node("FILTER", "b.x > 3", 5, vec![scan("a", &["x"], 10)]),
+            node("FILTER", "x > 3", 5, vec![index_scan]),
+        ] {
+            let mut plan = parsed(input);
+            fuse_scan_filters(&mut plan);
+            assert_eq!(plan.name, "FILTER");
+            assert!(plan.children[0].as_scan().unwrap().filters.is_empty());
+        }
+    }
+
+    #[test]
+    fn canonicalize_equates_reordered_inner_joins() {
+        let mut ab = parsed(inner_join(
+            &["a.id = b.id", "a.k = b.k"],
+            1,
+            scan("a", &["id", "k"], 10),
+            scan("b", &["id", "k"], 20),
+        ));
+        let mut ba = parsed(inner_join(
+            &["b.k = a.k", "b.id = a.id"],
+            1,
+            scan("b", &["id", "k"], 20),
+            scan("a", &["id", "k"], 10),
+        ));
+        canonicalize(&mut ab);
+        canonicalize(&mut ba);
+        assert_trees_eq(&ab, &ba);
+    }
+
+    #[test]
+    fn canonicalize_keeps_non_equi_lines_and_quoted_names() {
+        let mut plan = parsed(node(
+            "HASH_JOIN",
+            "INNER\n\"my.t\".x = b.x\na.y <= b.y\n",
+            1,
+            vec![scan("my.t", &["x"], 10), scan("b", &["x", "y"], 10)],
+        ));
+        canonicalize(&mut plan);
+        assert_eq!(plan.extra_info, "INNER\nb.x = \"my.t\".x\na.y <= b.y\n");
+
+        let mut reparsed = plan.clone();
+        parse_tree_extra_info(&mut reparsed).unwrap();
+        assert_eq!(
+            reparsed.as_join().unwrap().equalizers,
+            vec![Condition {
+                left_attr: Attribute::new("b", "x"),
+                right_attr: Attribute::new("my.t", "x"),
+            }]
+        );
+    }
+
+    #[test]
+    fn apply_schema_aliases_renames_everywhere() {
+        let mut plan = parsed(node(
+            "PROJECTION",
+            "t1.o_id\nt2.c_name",
+            1,
+            vec![inner_join(
+                &["t1.o_custkey = t2.c_custkey"],
+                1,
+                scan("t1", &["o_id", "o_custkey"], 1),
+                scan("t2", &["c_custkey", "c_name"], 1),
+            )],
+        ));
+        let aliases = HashMap::from([
+            ("t1".to_string(), "orders".to_string()),
+            ("t2".to_string(), "customer".to_string()),
+        ]);
+        apply_schema_aliases(&mut plan, &aliases);
+
+        let Some(Op::Project(project)) = &plan.attr else {
+            panic!("expected a projection");
+        };
+        assert_eq!(
+            project.columns,
+            vec![
+                Attribute::new("orders", "o_id"),
+                Attribute::new("customer", "c_name")
+            ]
+        );
+        let join = plan.children[0].as_join().unwrap();
+        assert_eq!(
+            join.equalizers[0].to_string(),
+            "orders.o_custkey = customer.c_custkey"
+        );
+        let scans: Vec<_> = plan.children[0]
+            .children
+            .iter()
+            .map(|c| c.as_scan().unwrap().table_name.as_str())
+            .collect();
+        assert_eq!(scans, vec!["orders", "customer"]);
+    }
+
+    #[test]
+    fn to_self_timing_subtracts_children_and_clamps() {
+        let mut plan = parsed(node(
+            "PROJECTION",
+            "a.id",
+            1,
+            vec![timed(
+                inner_join(
+                    &["b.id = c.id"],
+                    1,
+                    timed(
+                        inner_join(
+                            &["a.id = b.id"],
+                            1,
+                            timed(scan("a", &["id"], 1), 0.25),
+                            timed(scan("b", &["id"], 1), 0.25),
+                        ),
+                        0.75,
+                    ),
+                    timed(scan("c", &["id"], 1), 0.5),
+                ),
+                1.0,
+            )],
+        ));
+        assert_eq!(to_self_timing(&mut plan), 1);
+        let timings: Vec<_> = [0, 1, 2, 3, 4, 5]
+            .iter()
+            .map(|&id| plan.find(id).unwrap().timing)
+            .collect();
+        assert_eq!(
+            timings,
+            vec![
+                None,
+                Some(0.0),
+                Some(0.25),
+                Some(0.25),
+                Some(0.25),
+                Some(0.5)
+            ]
+        );
+    }
+
+    #[test]
+    fn fuse_join_aggregate_annotates_or_collapses() {
+        let grouped = |groups: &str| {
+            parsed(timed(
+                node(
+                    "HASH_GROUP_BY",
+                    groups,
+                    3,
+                    vec![timed(
+                        inner_join(
+                            &["a.id = b.id"],
+                            10,
+                            scan("a", &["id", "x"], 10),
+                            scan("b", &["id"], 10),
+                        ),
+                        0.5,
+                    )],
+                ),
+                0.25,
+            ))
+        };
+
+        let mut annotated = grouped("a.id\ncount_star()");
+        fuse_join_aggregate(&mut annotated, false);
+        assert_eq!(annotated.name, "HASH_GROUP_BY");
+        assert!(
+            annotated.children[0]
+                .as_join()
+                .unwrap()
+                .followed_by_aggregate
+        );
+
+        let mut collapsed = grouped("id\ncount_star()");
+        fuse_join_aggregate(&mut collapsed, true);
+        assert_eq!(collapsed.name, "HASH_JOIN");
+        assert_eq!(collapsed.cardinality, 3);
+        assert_eq!(collapsed.timing, Some(0.75));
+        assert!(collapsed.as_join().unwrap().followed_by_aggregate);
+
+        let mut not_keys = grouped("a.x\ncount_star()");
+        fuse_join_aggregate(&mut not_keys, true);
+        assert_eq!(not_keys.name, "HASH_GROUP_BY");
+        assert!(
+            !not_keys.children[0]
+                .as_join()
+                .unwrap()
+                .followed_by_aggregate
+        );
+    }
+
+    #[test]
+    fn slice_for_column_keeps_the_carrying_input() {
+        let plan = parsed(node(
+            "PROJECTION",
+            "a.x\nb.y",
+            1,
+            vec![inner_join(
+                &["a.id = b.id"],
+                1,
+                node("FILTER", "a.x > 3", 1, vec![scan("a", &["id", "x"], 1)]),
+                scan("b", &["id", "y"], 1),
+            )],
+        ));
+        let slice = slice_for_column(&plan, &Attribute::new("b", "y")).unwrap();
+        assert_eq!(slice.name, "PROJECTION");
+        let join = &slice.children[0];
+        // the join keeps its condition on a, which the slice no longer scans
+        assert_eq!(join.name, "HASH_JOIN");
+        let cond = &join.as_join().unwrap().equalizers[0];
+        assert_eq!(cond.left_attr, Attribute::new("a", "id"));
+        assert_eq!(cond.right_attr, Attribute::new("b", "id"));
+        assert_eq!(join.children.len(), 1);
+        assert_eq!(join.children[0].id, 4);
+        assert_eq!(join.children[0].as_scan().unwrap().table_name, "b");
+
+        assert!(slice_for_column(&plan, &Attribute::new("a", "id")).is_none());
+    }
+}
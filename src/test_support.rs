@@ -0,0 +1,179 @@
+//! Helpers for tests of code producing plan trees.
+
+use crate::{assign_ids, parse_tree_extra_info, Node, Op};
+
+/// An unparsed, unmeasured node as read from a profile.
+pub fn node(name: &str, extra_info: &str, cardinality: u64, children: Vec<Node>) -> Node {
+    Node {
+        id: 0,
+        name: name.to_string(),
+        timing: None,
+        total_timing: None,
+        cardinality,
+        cost: None,
+        exact_cardinality: None,
+        extra_info: extra_info.to_string(),
+        children: children.into_iter().map(Box::new).collect(),
+        attr: None,
+    }
+}
+
+/// A `SEQ_SCAN` of `columns` of `table`.
+pub fn scan(table: &str, columns: &[&str], cardinality: u64) -> Node {
+    let extra_info = format!("{}\n[INFOSEPARATOR]\n{}", table, columns.join("\n"));
+    node("SEQ_SCAN", &extra_info, cardinality, vec![])
+}
+
+/// An inner `HASH_JOIN` on `conditions` such as `a.id = b.id`.
+pub fn inner_join(conditions: &[&str], cardinality: u64, probe: Node, build: Node) -> Node {
+    let extra_info = format!("INNER\n{}\n", conditions.join("\n"));
+    node("HASH_JOIN", &extra_info, cardinality, vec![probe, build])
+}
+
+/// `node` with its `timing` set to `seconds`.
+pub fn timed(mut node: Node, seconds: f64) -> Node {
+    node.timing = Some(seconds);
+    node
+}
+
+/// The tree with every `extra_info` parsed and ids assigned, as `from_str`
+/// would load it. Panics if it doesn't parse.
+#[track_caller]
+pub fn parsed(mut root: Node) -> Node {
+    parse_tree_extra_info(&mut root).unwrap();
+    assign_ids(&mut root);
+    root
+}
+
+fn op_kind(op: &Option<Op>) -> &'static str {
+    match op {
+        Some(Op::Join(_)) => "Op::Join",
+        Some(Op::Scan(_)) => "Op::Scan",
+        Some(Op::Project(_)) => "Op::Project",
+        Some(Op::Aggregate(_)) => "Op::Aggregate",
+        Some(Op::Filter(_)) => "Op::Filter",
+        Some(Op::MultiwayJoin(_)) => "Op::MultiwayJoin",
+        Some(Op::CteScan(_)) => "Op::CteScan",
+        Some(Op::RecursiveCte(_)) => "Op::RecursiveCte",
+        None => "None",
+    }
+}
+
+/// Describes the first place, in preorder, where two trees differ in a way
+/// `Node`'s `PartialEq` notices, e.g.
+/// `children[0].children[1]: expected Op::Scan, got Op::Filter`.
+pub fn first_difference(expected: &Node, actual: &Node) -> Option<String> {
+    fn diff(expected: &Node, actual: &Node, path: &str) -> Option<String> {
+        let at = if path.is_empty() { "root" } else { path };
+        if expected.name != actual.name {
+            return Some(format!(
+                "{}: expected name {:?}, got {:?}",
+                at, expected.name, actual.name
+            ));
+        }
+        if expected.cardinality != actual.cardinality {
+            return Some(format!(
+                "{}: expected cardinality {}, got {}",
+                at, expected.cardinality, actual.cardinality
+            ));
+        }
+        if expected.exact_cardinality != actual.exact_cardinality {
+            return Some(format!(
+                "{}: expected exact {:?}, got {:?}",
+                at, expected.exact_cardinality, actual.exact_cardinality
+            ));
+        }
+        if op_kind(&expected.attr) != op_kind(&actual.attr) {
+            return Some(format!(
+                "{}: expected {}, got {}",
+                at,
+                op_kind(&expected.attr),
+                op_kind(&actual.attr)
+            ));
+        }
+        if expected.attr != actual.attr {
+            return Some(format!(
+                "{}: expected {:?}, got {:?}",
+                at, expected.attr, actual.attr
+            ));
+        }
+        if expected.extra_info != actual.extra_info {
+            return Some(format!(
+                "{}: expected extra_info {:?}, got {:?}",
+                at, expected.extra_info, actual.extra_info
+            ));
+        }
+        if expected.children.len() != actual.children.len() {
+            return Some(format!(
+                "{}: expected {} children, got {}",
+                at,
+                expected.children.len(),
+                actual.children.len()
+            ));
+        }
+        expected
+            .children
+            .iter()
+            .zip(&actual.children)
+            .enumerate()
+            .find_map(|(i, (e, a))| {
+                let child = if path.is_empty() {
+                    format!("children[{}]", i)
+                } else {
+                    format!("{}.children[{}]", path, i)
+                };
+                diff(e, a, &child)
+            })
+    }
+    diff(expected, actual, "")
+}
+
+/// Like `assert_eq!` on two trees, but on mismatch reports only the first
+/// differing node instead of both trees in full.
+#[track_caller]
+pub fn assert_trees_eq(expected: &Node, actual: &Node) {
+    if let Some(difference) = first_difference(expected, actual) {
+        panic!("trees differ at {}", difference);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(b_cardinality: u64) -> Node {
+        parsed(inner_join(
+            &["a.id = b.id"],
+            1,
+            scan("a", &["id"], 10),
+            scan("b", &["id"], b_cardinality),
+        ))
+    }
+
+    #[test]
+    fn first_difference_names_the_differing_node() {
+        assert_eq!(first_difference(&plan(5), &plan(5)), None);
+        assert_eq!(
+            first_difference(&plan(5), &plan(6)).as_deref(),
+            Some("children[1]: expected cardinality 5, got 6")
+        );
+        let mut renamed = plan(5);
+        renamed.name = "NESTED_LOOP_JOIN".to_string();
+        assert_eq!(
+            first_difference(&plan(5), &renamed).as_deref(),
+            Some("root: expected name \"HASH_JOIN\", got \"NESTED_LOOP_JOIN\"")
+        );
+        let mut unparsed = plan(5);
+        unparsed.children[0].attr = None;
+        assert_eq!(
+            first_difference(&plan(5), &unparsed).as_deref(),
+            Some("children[0]: expected Op::Scan, got None")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "trees differ at children[1]: expected cardinality 5, got 6")]
+    fn assert_trees_eq_reports_the_first_difference() {
+        assert_trees_eq(&plan(5), &plan(6));
+    }
+}
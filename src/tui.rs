@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::io;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::Frame;
+
+use crate::{Node, NodeId};
+
+struct Row<'a> {
+    node: &'a Node,
+    depth: usize,
+}
+
+struct Browser<'a> {
+    root: &'a Node,
+    collapsed: HashSet<NodeId>,
+    state: ListState,
+}
+
+impl<'a> Browser<'a> {
+    fn rows(&self) -> Vec<Row<'a>> {
+        fn visit<'a>(
+            node: &'a Node,
+            depth: usize,
+            collapsed: &HashSet<NodeId>,
+            rows: &mut Vec<Row<'a>>,
+        ) {
+            rows.push(Row { node, depth });
+            if !collapsed.contains(&node.id) {
+                for child in &node.children {
+                    visit(child, depth + 1, collapsed, rows);
+                }
+            }
+        }
+        let mut rows = Vec::new();
+        visit(self.root, 0, &self.collapsed, &mut rows);
+        rows
+    }
+
+    fn selected(&self, rows: &[Row<'a>]) -> &'a Node {
+        rows[self.state.selected().unwrap_or(0).min(rows.len() - 1)].node
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let rows = self.rows();
+        let [tree_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(frame.area());
+
+        let items: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                let marker = if row.node.children.is_empty() {
+                    " "
+                } else if self.collapsed.contains(&row.node.id) {
+                    "▸"
+                } else {
+                    "▾"
+                };
+                ListItem::new(format!(
+                    "{}{} {} ({})",
+                    "  ".repeat(row.depth),
+                    marker,
+                    row.node.summary(),
+                    row.node.cardinality
+                ))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title("plan (q to quit)"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, tree_area, &mut self.state);
+
+        let node = self.selected(&rows);
+        let detail = format!(
+            "id: {}\nname: {}\ncardinality: {}\ntiming: {}\n\n{:#?}",
+            node.id,
+            node.name,
+            node.cardinality,
+            node.timing.map_or("-".to_string(), |t| t.to_string()),
+            node.attr
+        );
+        let paragraph = Paragraph::new(detail)
+            .block(Block::bordered().title("node"))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, detail_area);
+    }
+
+    fn toggle(&mut self, id: NodeId) {
+        if !self.collapsed.remove(&id) {
+            self.collapsed.insert(id);
+        }
+    }
+
+    // returns false when the user asked to quit
+    fn handle(&mut self, code: KeyCode) -> bool {
+        let rows = self.rows();
+        let node = self.selected(&rows);
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.state.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.state.select_previous(),
+            KeyCode::Left | KeyCode::Char('h') => {
+                self.collapsed.insert(node.id);
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                self.collapsed.remove(&node.id);
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => self.toggle(node.id),
+            _ => {}
+        }
+        // keep the selection on a visible row after collapsing
+        let len = self.rows().len();
+        if self.state.selected().is_some_and(|i| i >= len) {
+            self.state.select(Some(len - 1));
+        }
+        true
+    }
+}
+
+/// Opens an interactive terminal view of the plan. Arrow keys (or hjkl) move
+/// and collapse/expand subtrees, enter toggles, q quits.
+pub fn browse(root: &Node) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let mut browser = Browser {
+        root,
+        collapsed: HashSet::new(),
+        state: ListState::default().with_selected(Some(0)),
+    };
+    let result = loop {
+        if let Err(e) = terminal.draw(|frame| browser.draw(frame)) {
+            break Err(e);
+        }
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                if !browser.handle(key.code) {
+                    break Ok(());
+                }
+            }
+            Ok(_) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    ratatui::restore();
+    result
+}
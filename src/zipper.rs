@@ -0,0 +1,145 @@
+use crate::Node;
+
+// an ancestor of the focus, with the child on the way down taken out
+struct Crumb {
+    parent: Box<Node>,
+    index: usize,
+}
+
+/// A cursor into an owned plan that can move around the tree and replace
+/// the subtree under it, without parent pointers. A move that isn't possible
+/// returns `false` and leaves the cursor where it was. `finish` zips the tree
+/// back up and returns its root.
+pub struct Zipper {
+    focus: Box<Node>,
+    path: Vec<Crumb>,
+}
+
+impl Zipper {
+    /// A cursor on `root`.
+    pub fn new(root: Node) -> Self {
+        Zipper {
+            focus: Box::new(root),
+            path: Vec::new(),
+        }
+    }
+
+    pub fn current(&self) -> &Node {
+        &self.focus
+    }
+
+    pub fn current_mut(&mut self) -> &mut Node {
+        &mut self.focus
+    }
+
+    /// Distance from the root to the current node.
+    pub fn depth(&self) -> usize {
+        self.path.len()
+    }
+
+    /// Moves to the parent.
+    pub fn up(&mut self) -> bool {
+        let Some(Crumb { parent, index }) = self.path.pop() else {
+            return false;
+        };
+        let child = std::mem::replace(&mut self.focus, parent);
+        self.focus.children.insert(index, child);
+        true
+    }
+
+    /// Moves to the `i`-th child.
+    pub fn down(&mut self, i: usize) -> bool {
+        if i >= self.focus.children.len() {
+            return false;
+        }
+        let child = self.focus.children.remove(i);
+        let parent = std::mem::replace(&mut self.focus, child);
+        self.path.push(Crumb { parent, index: i });
+        true
+    }
+
+    /// Moves to the previous sibling.
+    pub fn left(&mut self) -> bool {
+        match self.path.last() {
+            Some(crumb) if crumb.index > 0 => {
+                let i = crumb.index - 1;
+                self.up() && self.down(i)
+            }
+            _ => false,
+        }
+    }
+
+    /// Moves to the next sibling.
+    pub fn right(&mut self) -> bool {
+        match self.path.last() {
+            // the parent's children don't include the focus
+            Some(crumb) if crumb.index < crumb.parent.children.len() => {
+                let i = crumb.index + 1;
+                self.up() && self.down(i)
+            }
+            _ => false,
+        }
+    }
+
+    /// Replaces the subtree under the cursor, returning the old one.
+    pub fn replace(&mut self, node: Node) -> Node {
+        *std::mem::replace(&mut self.focus, Box::new(node))
+    }
+
+    /// The whole tree, with all edits applied.
+    pub fn finish(mut self) -> Node {
+        while self.up() {}
+        *self.focus
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+
+    fn plan() -> Node {
+        parsed(inner_join(
+            &["a.id = b.id"],
+            1,
+            scan("a", &["id"], 10),
+            scan("b", &["id"], 20),
+        ))
+    }
+
+    #[test]
+    fn edits_a_leaf_and_zips_back_up() {
+        let mut zipper = Zipper::new(plan());
+        assert!(zipper.down(1));
+        assert_eq!(zipper.depth(), 1);
+        zipper.current_mut().cardinality = 30;
+        let old = zipper.replace(scan("c", &["id"], 5));
+        assert_eq!(old.cardinality, 30);
+
+        let root = zipper.finish();
+        assert_eq!(root.name, "HASH_JOIN");
+        assert_eq!(root.children.len(), 2);
+        assert_eq!(root.children[0].cardinality, 10);
+        assert_eq!(root.children[1].extra_info, "c\n[INFOSEPARATOR]\nid");
+    }
+
+    #[test]
+    fn moves_that_are_not_possible_stay_put() {
+        let mut zipper = Zipper::new(plan());
+        assert!(!zipper.up());
+        assert!(!zipper.left());
+        assert!(!zipper.right());
+        assert!(!zipper.down(2));
+
+        assert!(zipper.down(0));
+        assert!(!zipper.left());
+        assert!(zipper.right());
+        assert_eq!(zipper.current().cardinality, 20);
+        assert!(!zipper.right());
+        assert_eq!(zipper.current().cardinality, 20);
+        assert!(zipper.left());
+        assert_eq!(zipper.current().cardinality, 10);
+        assert!(!zipper.down(0));
+        assert_eq!(zipper.finish(), plan());
+    }
+}